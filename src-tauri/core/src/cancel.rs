@@ -0,0 +1,102 @@
+use crate::error::Error;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// How often a running command is checked for cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// Time a cancelled command gets to exit on its own before it is killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(1);
+
+/// Flag shared between an operation and whoever may want to stop it.
+/// Clones share the flag, the default token is simply never cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Fails with a `Cancelled` error once cancelled, so loops can bail out with `?`.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            return Err(Error::cancelled());
+        }
+        Ok(())
+    }
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+// SIGTERM lets pkexec close its dialog and flatpak-spawn pass the signal on to the host
+#[cfg(unix)]
+fn terminate(child: &mut std::process::Child) {
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    let started = Instant::now();
+    while started.elapsed() < TERMINATE_GRACE {
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &mut std::process::Child) {
+    let _ = child.kill();
+}
+
+/// Runs a command to completion like `Command::output`, but stops it as soon as
+/// `cancel` is set. `program` names the tool in errors, as in `Error::spawn`.
+pub fn output(command: &mut Command, program: &str, cancel: &CancelToken) -> Result<Output, Error> {
+    cancel.check()?;
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::spawn(program, e))?;
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let status = loop {
+        if cancel.is_cancelled() {
+            terminate(&mut child);
+            let _ = child.wait();
+            return Err(Error::cancelled().with("tool", program));
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(Error::spawn(program, e)),
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
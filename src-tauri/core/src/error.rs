@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Stable, machine-readable reason for a failure. The frontend and the command line
+/// switch on these, so existing values must not be renamed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    PermissionDenied, // Needs elevation, e.g. a root-owned file or an HKLM value
+    NotFound,
+    NotManaged,    // Exists, but isn't something Zen can change
+    AuthCancelled, // The password dialog was dismissed or refused
+    ToolMissing,   // A helper like systemctl or pkexec isn't installed
+    ParseError,
+    Unsupported, // Not available on this platform
+    Cancelled,   // Stopped on request before it finished
+    Failed,
+}
+
+/// Error returned by every command. Serializes as
+/// `{ "code": "not_found", "message": "...", "context": { "path": "..." } }`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub message: String, // English, for logs and as a fallback for the UI
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context: BTreeMap<String, String>, // e.g. "path", "unit", "tool", "exit_code"
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Error {
+            code,
+            message: message.into(),
+            context: BTreeMap::new(),
+        }
+    }
+
+    /// Adds one context value, e.g. the path or unit the error is about.
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.context.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn failed(message: impl Into<String>) -> Self {
+        Error::new(ErrorCode::Failed, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Error::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn not_managed(message: impl Into<String>) -> Self {
+        Error::new(ErrorCode::NotManaged, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Error::new(ErrorCode::ParseError, message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Error::new(ErrorCode::Unsupported, message)
+    }
+
+    pub fn cancelled() -> Self {
+        Error::new(ErrorCode::Cancelled, "The operation was cancelled")
+    }
+
+    /// Maps a file system error, remembering which path it happened on.
+    pub fn io(error: std::io::Error, path: &std::path::Path) -> Self {
+        Error::from(error).with("path", path.display())
+    }
+
+    /// Error for a helper program that couldn't be started at all.
+    pub fn spawn(program: &str, error: std::io::Error) -> Self {
+        let error = if error.kind() == std::io::ErrorKind::NotFound {
+            Error::new(
+                ErrorCode::ToolMissing,
+                format!("{} is not installed", program),
+            )
+        } else {
+            Error::from(error)
+        };
+        error.with("tool", program)
+    }
+
+    /// Error for a helper program that ran but exited unsuccessfully, classified by
+    /// its exit code and what it printed. `program` is the tool that did the work,
+    /// e.g. "systemctl" or "pkexec", even when it ran through flatpak-spawn.
+    pub fn command(program: &str, output: &std::process::Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let lower = stderr.to_lowercase();
+        let exit_code = output.status.code();
+        // pkexec exits with 126 when the dialog was dismissed and 127 when not authorized
+        let code = if program == "pkexec" && matches!(exit_code, Some(126) | Some(127)) {
+            ErrorCode::AuthCancelled
+        } else if lower.contains("access denied")
+            || lower.contains("permission denied")
+            || lower.contains("interactive authentication required")
+        {
+            ErrorCode::PermissionDenied
+        } else if lower.contains("not found") || lower.contains("does not exist") {
+            ErrorCode::NotFound
+        } else {
+            ErrorCode::Failed
+        };
+
+        let message = match (code, stderr.is_empty()) {
+            (ErrorCode::AuthCancelled, true) => "Authentication was cancelled".to_string(),
+            (_, true) => format!("{} failed", program),
+            (_, false) => stderr,
+        };
+        let error = Error::new(code, message).with("tool", program);
+        match exit_code {
+            Some(exit_code) => error.with("exit_code", exit_code),
+            None => error,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let code = match error.kind() {
+            ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+            ErrorKind::NotFound => ErrorCode::NotFound,
+            ErrorKind::InvalidData => ErrorCode::ParseError,
+            ErrorKind::Unsupported => ErrorCode::Unsupported,
+            _ => ErrorCode::Failed,
+        };
+        Error::new(code, error.to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,124 @@
+//! Command lines of desktop entries, split the way the Desktop Entry spec describes
+//! for the `Exec` key.
+
+// Undoes the escapes every string value may carry, which the spec applies before quoting
+fn unescape_value(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            // Not a string escape, so it belongs to the quoting rules
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Splits an Exec value into arguments.
+///
+/// Arguments are separated by whitespace and may be wrapped in double quotes, inside
+/// which `\"`, `` \` ``, `\$` and `\\` stand for the character itself. Single quotes
+/// are not part of the spec, but hand-written entries use them, so they group an
+/// argument too. Field codes are left in place.
+pub fn split_exec(exec: &str) -> Vec<String> {
+    let value = unescape_value(exec);
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some('"') if c == '\\' => match chars.peek() {
+                Some(&next) if matches!(next, '"' | '`' | '$' | '\\') => {
+                    current.push(next);
+                    chars.next();
+                }
+                _ => current.push(c),
+            },
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(
+            split_exec("  app  --flag\tvalue "),
+            ["app", "--flag", "value"]
+        );
+        assert!(split_exec("   ").is_empty());
+    }
+
+    #[test]
+    fn keeps_quoted_arguments_together() {
+        assert_eq!(
+            split_exec(r#""/opt/My App/run" --name "two words""#),
+            ["/opt/My App/run", "--name", "two words"]
+        );
+        assert_eq!(split_exec(r#"app """#), ["app", ""]);
+        assert_eq!(
+            split_exec("sh -c 'sleep 5; app'"),
+            ["sh", "-c", "sleep 5; app"]
+        );
+    }
+
+    #[test]
+    fn handles_escapes_inside_double_quotes() {
+        assert_eq!(
+            split_exec(r#"echo "say \"hi\" for \$5 \`now\`""#),
+            ["echo", r#"say "hi" for $5 `now`"#]
+        );
+        // `\\\\` in the file is one literal backslash once both rules are applied
+        assert_eq!(split_exec(r#"echo "a\\\\b""#), ["echo", r"a\b"]);
+        assert_eq!(split_exec(r#"echo "a\qb""#), ["echo", r"a\qb"]);
+    }
+
+    #[test]
+    fn applies_string_escapes_first() {
+        assert_eq!(split_exec(r"app\sone two"), ["app", "one", "two"]);
+        assert_eq!(split_exec(r#"app "one\stwo""#), ["app", "one two"]);
+    }
+
+    #[test]
+    fn leaves_field_codes_alone() {
+        assert_eq!(split_exec("app %U --x=%%"), ["app", "%U", "--x=%%"]);
+    }
+}
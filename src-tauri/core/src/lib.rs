@@ -0,0 +1,15 @@
+//! Discovery, toggling, creation and deletion of startup entries and services.
+//! Shared by the Zen App Manager window and command line, and free of any Tauri code.
+
+pub mod cancel;
+pub mod error;
+pub mod exec;
+pub mod process;
+pub mod services;
+pub mod startup;
+#[cfg(target_os = "linux")]
+pub mod systemd;
+pub mod unit_file;
+
+pub use cancel::CancelToken;
+pub use error::{Error, ErrorCode};
@@ -0,0 +1,433 @@
+// Only Linux exposes /proc, elsewhere most of this goes unused
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use crate::exec;
+use crate::startup::{self, StartupApp};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RuntimeStatus {
+    pub running: bool,
+    pub pids: Vec<u32>,
+    pub uptime_secs: Option<u64>, // Since the oldest matching process started
+    pub matched_by: Option<String>, // "cgroup", "exe" or "cmdline"
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub exe: Option<PathBuf>,
+    pub cmdline: Vec<String>,
+    pub cgroup: String,
+    pub start_ticks: u64, // Clock ticks after boot
+}
+
+/// Kernel clock ticks per second, the unit of every time in `/proc/<pid>/stat`.
+#[cfg(unix)]
+pub fn clock_ticks() -> u64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as u64
+    } else {
+        100
+    }
+}
+
+#[cfg(not(unix))]
+pub fn clock_ticks() -> u64 {
+    100
+}
+
+/// Seconds since boot, from `/proc/uptime`.
+pub fn system_uptime() -> Option<f64> {
+    fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Fields of `/proc/<pid>/stat` after the command name, so index 0 is the
+/// state (field 3 in proc(5)) and field N lives at index N - 3.
+pub fn stat_fields(pid: u32) -> Option<(String, Vec<String>)> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The name may contain spaces and parentheses, so split around the last ')'
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let comm = stat.get(open + 1..close)?.to_string();
+    let fields = stat
+        .get(close + 1..)?
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    Some((comm, fields))
+}
+
+#[cfg(unix)]
+fn owned_by_us(pid: u32) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let uid = unsafe { libc::getuid() };
+    fs::metadata(format!("/proc/{}", pid))
+        .map(|m| m.uid() == uid)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn owned_by_us(_pid: u32) -> bool {
+    false
+}
+
+pub fn read_process(pid: u32) -> Option<ProcessInfo> {
+    let (_, fields) = stat_fields(pid)?;
+    let exe = fs::read_link(format!("/proc/{}/exe", pid)).ok().map(|p| {
+        // Binaries replaced on disk show up as "/path (deleted)"
+        let s = p.to_string_lossy();
+        PathBuf::from(s.strip_suffix(" (deleted)").unwrap_or(&s).to_string())
+    });
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|raw| {
+            raw.split(|b| *b == 0)
+                .filter(|a| !a.is_empty())
+                .map(|a| String::from_utf8_lossy(a).to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(ProcessInfo {
+        pid,
+        ppid: fields.get(1)?.parse().ok()?,
+        exe,
+        cmdline,
+        cgroup: fs::read_to_string(format!("/proc/{}/cgroup", pid)).unwrap_or_default(),
+        start_ticks: fields.get(19)?.parse().ok()?,
+    })
+}
+
+/// Every process of the current user.
+pub fn list_processes() -> Vec<ProcessInfo> {
+    let own_pid = std::process::id();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_string_lossy().parse::<u32>().ok())
+        .filter(|pid| *pid != own_pid && owned_by_us(*pid))
+        .filter_map(read_process)
+        .collect()
+}
+
+/// Seconds a process has been running.
+pub fn process_uptime(process: &ProcessInfo) -> Option<u64> {
+    let started = process.start_ticks as f64 / clock_ticks() as f64;
+    system_uptime().map(|now| (now - started).max(0.0) as u64)
+}
+
+/// Unit name systemd-xdg-autostart-generator gives a desktop entry, e.g. `app-discord@autostart.service`.
+pub fn autostart_unit(desktop_id: &str) -> String {
+    let stem = desktop_id.strip_suffix(".desktop").unwrap_or(desktop_id);
+    let escaped: String = stem
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b == b':' || b == b'_' || b == b'.' {
+                (b as char).to_string()
+            } else {
+                format!("\\x{:02x}", b)
+            }
+        })
+        .collect();
+    format!("app-{}@autostart.service", escaped)
+}
+
+// Programs that run something else, so their name alone says nothing about the entry
+const WRAPPERS: &[&str] = &[
+    "sh", "bash", "dash", "zsh", "fish", "env", "flatpak", "perl", "ruby", "node", "java",
+];
+
+fn is_wrapper(program: &str) -> bool {
+    let base = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    WRAPPERS.contains(&base.as_str()) || base.starts_with("python")
+}
+
+/// Splits an entry's command line into the arguments its process would get.
+/// Field codes like `%U` are dropped, `%%` becomes `%` and leading `env NAME=value`
+/// assignments are skipped.
+fn exec_args(command: &str) -> Vec<String> {
+    let mut args: Vec<String> = exec::split_exec(command)
+        .into_iter()
+        .filter(|a| a == "%%" || !(a.len() == 2 && a.starts_with('%')))
+        .map(|a| a.replace("%%", "%"))
+        .collect();
+
+    // `env -i ...` and the like stay as they are, env is a wrapper then
+    let mut start = 0;
+    if args.first().is_some_and(|a| a == "env") && args.get(1).is_some_and(|a| !a.starts_with('-'))
+    {
+        start = 1;
+    }
+    while args.get(start).is_some_and(|a| {
+        a.split_once('=')
+            .is_some_and(|(name, _)| !name.is_empty() && !name.contains('/'))
+    }) {
+        start += 1;
+    }
+    args.split_off(start)
+}
+
+/// What the processes of one startup entry look like, worked out once per listing.
+struct Pattern {
+    unit: String, // Cgroup unit of the systemd autostart service
+    flatpak_scope: Option<String>,
+    args: Vec<String>,
+    resolved: Option<PathBuf>, // The program with symlinks resolved
+    strict: bool,              // The arguments have to match as well
+}
+
+impl Pattern {
+    fn new(app: &StartupApp) -> Self {
+        let command = if app.full_command.is_empty() {
+            &app.command
+        } else {
+            &app.full_command
+        };
+        let args = exec_args(command);
+        let program = args.first().map(String::as_str).unwrap_or_default();
+        // Flatpak apps get a scope of their own, the `flatpak run` process doesn't last
+        let flatpak_scope = (Path::new(program).ends_with("flatpak")
+            && args.get(1).is_some_and(|a| a == "run"))
+        .then(|| args[2..].iter().find(|a| !a.starts_with('-')))
+        .flatten()
+        .map(|id| format!("app-flatpak-{}-", id));
+        Pattern {
+            unit: autostart_unit(&app.id),
+            flatpak_scope,
+            resolved: startup::resolve_executable(program).and_then(|p| fs::canonicalize(p).ok()),
+            // A shell or interpreter alone says nothing, and neither does a program
+            // the entry starts with particular arguments
+            strict: is_wrapper(program) || args.len() > 1,
+            args,
+        }
+    }
+}
+
+fn same_path(arg: &str, path: &Path) -> bool {
+    fs::canonicalize(arg).is_ok_and(|p| p == path)
+}
+
+fn same_args(expected: &[String], actual: &[String]) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .all(|(e, a)| e == a || fs::canonicalize(e).is_ok_and(|e| same_path(a, &e)))
+}
+
+/// Checks whether a process belongs to a startup entry, and how we could tell.
+fn match_process(pattern: &Pattern, process: &ProcessInfo) -> Option<&'static str> {
+    if process.cgroup.contains(&pattern.unit) {
+        return Some("cgroup");
+    }
+    if let Some(scope) = &pattern.flatpak_scope {
+        return process.cgroup.contains(scope.as_str()).then_some("cgroup");
+    }
+
+    let (program, args) = pattern.args.split_first()?;
+    let resolved = pattern.resolved.as_deref();
+    // The kernel already reports /proc/<pid>/exe fully resolved
+    let (how, rest) = if resolved.is_some() && process.exe.as_deref() == resolved {
+        ("exe", process.cmdline.get(1..))
+    } else if process.cmdline.first().is_some_and(|arg0| {
+        // A bare name may have been found anywhere on the PATH
+        arg0 == program || (!program.contains('/') && Path::new(arg0).ends_with(program))
+    }) {
+        ("cmdline", process.cmdline.get(1..))
+    } else if process
+        .cmdline
+        .get(1)
+        .zip(resolved)
+        .is_some_and(|(arg1, resolved)| same_path(arg1, resolved))
+    {
+        // Scripts show up as "<interpreter> <script>"
+        ("cmdline", process.cmdline.get(2..))
+    } else {
+        return None;
+    };
+    if pattern.strict && !same_args(args, rest.unwrap_or_default()) {
+        return None;
+    }
+    Some(how)
+}
+
+/// Finds the processes of one entry in a process listing.
+pub fn matching_processes<'a>(
+    app: &StartupApp,
+    processes: &'a [ProcessInfo],
+) -> Vec<(&'a ProcessInfo, &'static str)> {
+    let pattern = Pattern::new(app);
+    processes
+        .iter()
+        .filter_map(|p| match_process(&pattern, p).map(|how| (p, how)))
+        .collect()
+}
+
+/// Builds the runtime status of one entry from a process listing.
+pub fn runtime_status(app: &StartupApp, processes: &[ProcessInfo]) -> RuntimeStatus {
+    let matched = matching_processes(app, processes);
+    let oldest = matched.iter().map(|(p, _)| p).min_by_key(|p| p.start_ticks);
+    RuntimeStatus {
+        running: !matched.is_empty(),
+        pids: matched.iter().map(|(p, _)| p.pid).collect(),
+        uptime_secs: oldest.and_then(|p| process_uptime(p)),
+        matched_by: matched.first().map(|(_, how)| how.to_string()),
+    }
+}
+
+/// Fills in `runtime` for every entry.
+#[cfg(target_os = "linux")]
+pub fn annotate(apps: &mut [StartupApp]) {
+    let processes = list_processes();
+    for app in apps.iter_mut() {
+        app.runtime = Some(runtime_status(app, &processes));
+    }
+}
+
+// No /proc to look at, so leave the status unknown
+#[cfg(not(target_os = "linux"))]
+pub fn annotate(_apps: &mut [StartupApp]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(id: &str, command: &str) -> StartupApp {
+        StartupApp {
+            id: id.to_string(),
+            name: id.to_string(),
+            command: command.to_string(),
+            full_command: command.to_string(),
+            enabled: true,
+            path: PathBuf::from("/autostart").join(id),
+            size: "Unknown".to_string(),
+            location: "User".to_string(),
+            publisher: "Unknown".to_string(),
+            runtime: None,
+        }
+    }
+
+    fn process(exe: &Path, cmdline: &[&str], cgroup: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid: 100,
+            ppid: 1,
+            exe: Some(exe.to_path_buf()),
+            cmdline: cmdline.iter().map(|a| a.to_string()).collect(),
+            cgroup: cgroup.to_string(),
+            start_ticks: 0,
+        }
+    }
+
+    fn matches(app: &StartupApp, process: &ProcessInfo) -> Option<&'static str> {
+        match_process(&Pattern::new(app), process)
+    }
+
+    // An executable file to point entries at, canonical so it compares like /proc/<pid>/exe
+    fn program(dir: &tempfile::TempDir, name: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, "").unwrap();
+        fs::canonicalize(path).unwrap()
+    }
+
+    #[test]
+    fn exec_args_drops_codes_and_env() {
+        assert_eq!(
+            exec_args(r#"env GDK_SCALE=2 "/opt/My App/app" --minimized %U"#),
+            ["/opt/My App/app", "--minimized"]
+        );
+        assert_eq!(exec_args("app --level=100%%"), ["app", "--level=100%"]);
+        assert_eq!(exec_args("env -i app"), ["env", "-i", "app"]);
+    }
+
+    #[test]
+    fn matches_by_exe() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = program(&dir, "tool");
+        let entry = app("tool.desktop", &exe.to_string_lossy());
+        let found = process(&exe, &["tool-renamed-itself"], "0::/user.slice");
+        assert_eq!(matches(&entry, &found), Some("exe"));
+    }
+
+    #[test]
+    fn matches_a_script_run_by_its_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = program(&dir, "tool.py");
+        let script = script.to_string_lossy();
+        let python = Path::new("/usr/bin/python3");
+
+        let entry = app("tool.desktop", &script);
+        let found = process(python, &["python3", &script], "0::/user.slice");
+        assert_eq!(matches(&entry, &found), Some("cmdline"));
+
+        // With arguments in the entry, the process has to have them too
+        let entry = app("tool.desktop", &format!("{} --quiet", script));
+        let found = process(python, &["python3", &script, "--quiet"], "");
+        assert_eq!(matches(&entry, &found), Some("cmdline"));
+        let other = process(python, &["python3", &script, "--verbose"], "");
+        assert_eq!(matches(&entry, &other), None);
+    }
+
+    #[test]
+    fn matches_by_autostart_cgroup() {
+        let entry = app("discord.desktop", "/nonexistent/discord");
+        let cgroup = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-discord@autostart.service";
+        let found = process(Path::new("/opt/discord/Discord"), &["Discord"], cgroup);
+        assert_eq!(matches(&entry, &found), Some("cgroup"));
+    }
+
+    #[test]
+    fn matches_a_flatpak_only_by_its_scope() {
+        let entry = app(
+            "chat.desktop",
+            "flatpak run --branch=stable org.example.Chat",
+        );
+        let pattern = Pattern::new(&entry);
+        assert_eq!(
+            pattern.flatpak_scope.as_deref(),
+            Some("app-flatpak-org.example.Chat-")
+        );
+        let scope = "0::/user.slice/app.slice/app-flatpak-org.example.Chat-1234.scope";
+        let found = process(Path::new("/app/bin/chat"), &["chat"], scope);
+        assert_eq!(match_process(&pattern, &found), Some("cgroup"));
+        let outside = process(Path::new("/usr/bin/flatpak"), &["flatpak", "run"], "");
+        assert_eq!(match_process(&pattern, &outside), None);
+    }
+
+    #[test]
+    fn ignores_unrelated_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let exe = program(&dir, "tool");
+        let entry = app("tool.desktop", &exe.to_string_lossy());
+        let other = process(
+            Path::new("/usr/bin/other"),
+            &["other", "tool"],
+            "0::/user.slice",
+        );
+        assert_eq!(matches(&entry, &other), None);
+
+        // A shell alone isn't enough, its arguments have to match
+        let entry = app("sync.desktop", "sh -c 'sleep 5; sync-notes'");
+        let shell = process(Path::new("/usr/bin/bash"), &["sh", "-c", "other"], "");
+        assert_eq!(matches(&entry, &shell), None);
+        let ours = process(
+            Path::new("/usr/bin/bash"),
+            &["sh", "-c", "sleep 5; sync-notes"],
+            "",
+        );
+        assert!(matches(&entry, &ours).is_some());
+    }
+}
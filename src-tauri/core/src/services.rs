@@ -0,0 +1,549 @@
+use crate::cancel::CancelToken;
+use crate::error::Error;
+#[cfg(target_os = "linux")]
+use crate::error::ErrorCode;
+#[cfg(target_os = "linux")]
+use crate::systemd::{self, Bus, Systemd};
+use crate::unit_file;
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+
+/// A service unit file and, when systemd could load it, what it is doing right now.
+/// The optional fields are missing from snapshots and bundles saved by older versions.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Service {
+    pub name: String,
+    pub state: String, // Unit-file state: "enabled", "disabled", "static", "masked", ...
+    #[serde(default)]
+    pub preset: Option<String>, // What the distribution's preset files ask for
+    #[serde(default)]
+    pub active_state: Option<String>, // "active", "inactive", "failed", ...
+    #[serde(default)]
+    pub sub_state: Option<String>, // "running", "exited", "dead", ...
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub masked: bool, // Linked to /dev/null, nothing can start it until it is unmasked
+}
+
+/// Something to do to a running (or stopped) service right now.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+}
+
+impl ServiceAction {
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "start" => Some(ServiceAction::Start),
+            "stop" => Some(ServiceAction::Stop),
+            "restart" => Some(ServiceAction::Restart),
+            "reload" => Some(ServiceAction::Reload),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ServiceAction::Start => "start",
+            ServiceAction::Stop => "stop",
+            ServiceAction::Restart => "restart",
+            ServiceAction::Reload => "reload",
+        }
+    }
+
+    /// The action `systemctl enable --now` or `disable --now` adds.
+    pub fn for_enabled(enable: bool) -> Self {
+        if enable {
+            ServiceAction::Start
+        } else {
+            ServiceAction::Stop
+        }
+    }
+}
+
+/// Where a service ended up after a `ServiceAction`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RuntimeState {
+    pub active_state: String, // "active", "inactive", "failed", or "activating" if still busy
+    pub sub_state: String,
+}
+
+impl Service {
+    /// Whether enabling or disabling is all it takes to restore `state`.
+    /// Static, masked, generated and similar units can't be toggled.
+    pub fn is_toggleable(state: &str) -> bool {
+        state == "enabled" || state == "disabled"
+    }
+}
+
+pub fn is_flatpak() -> bool {
+    std::path::Path::new("/.flatpak-info").exists()
+}
+
+/// How system-wide service changes get their privileges, as the audit log records it.
+/// systemd asks polkit itself, inside Flatpak too.
+pub fn privilege_path() -> &'static str {
+    "polkit"
+}
+
+#[cfg(target_os = "linux")]
+fn bus(user: bool) -> Bus {
+    if user {
+        Bus::User
+    } else {
+        Bus::System
+    }
+}
+
+/// Current unit-file state, the same value `systemctl is-enabled` prints.
+#[cfg(target_os = "linux")]
+pub fn unit_file_state(name: &str, user: bool) -> Option<String> {
+    Systemd::connect(bus(user))
+        .and_then(|systemd| systemd.unit_file_state(name))
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn unit_file_state(_name: &str, _user: bool) -> Option<String> {
+    None
+}
+
+/// Current active state, e.g. "active", "inactive" or "failed".
+#[cfg(target_os = "linux")]
+pub fn active_state(name: &str, user: bool) -> Option<String> {
+    let value = Systemd::connect(bus(user))
+        .and_then(|systemd| systemd.unit_property(name, systemd::UNIT_INTERFACE, "ActiveState"))
+        .ok()?;
+    String::try_from(value).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn active_state(_name: &str, _user: bool) -> Option<String> {
+    None
+}
+
+// A property the way `systemctl show` prints it: numbers in decimal, lists space-separated
+#[cfg(target_os = "linux")]
+fn property_text(value: &zbus::zvariant::Value) -> String {
+    use zbus::zvariant::Value;
+    match value {
+        Value::Str(s) => s.to_string(),
+        Value::Bool(b) => if *b { "yes" } else { "no" }.to_string(),
+        Value::U32(n) => n.to_string(),
+        Value::U64(n) => n.to_string(),
+        Value::I32(n) => n.to_string(),
+        Value::I64(n) => n.to_string(),
+        Value::Array(items) => items
+            .iter()
+            .map(property_text)
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn property_map(
+    properties: HashMap<String, zbus::zvariant::OwnedValue>,
+    wanted: &[&str],
+) -> HashMap<String, String> {
+    properties
+        .into_iter()
+        .filter(|(key, _)| wanted.contains(&key.as_str()))
+        .map(|(key, value)| (key, property_text(&value)))
+        .collect()
+}
+
+/// Properties of the system manager, e.g. its boot timestamps, as text the way
+/// `systemctl show` prints them. Only the properties named in `wanted` are kept.
+#[cfg(target_os = "linux")]
+pub fn manager_properties(wanted: &[&str]) -> Result<HashMap<String, String>, Error> {
+    let systemd = Systemd::connect(Bus::System)?;
+    let path = zbus::zvariant::ObjectPath::from_static_str_unchecked(systemd::MANAGER_PATH);
+    let properties = systemd.properties(&path, systemd::MANAGER_INTERFACE)?;
+    Ok(property_map(properties, wanted))
+}
+
+/// The same for every unit the system manager has loaded, like `systemctl show '*'`.
+#[cfg(target_os = "linux")]
+pub fn loaded_unit_properties(wanted: &[&str]) -> Result<Vec<HashMap<String, String>>, Error> {
+    let systemd = Systemd::connect(Bus::System)?;
+    Ok(systemd
+        .list_units()?
+        .into_iter()
+        // Units can go away between listing and reading them
+        .filter_map(|unit| systemd.properties(&unit.path, systemd::UNIT_INTERFACE).ok())
+        .map(|properties| property_map(properties, wanted))
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+fn list_unit_files(user: bool) -> Result<Vec<Service>, Error> {
+    let systemd = Systemd::connect(bus(user))?;
+    let files = systemd.list_unit_files()?;
+    // One call for everything loaded instead of a round trip per unit
+    let units: HashMap<String, systemd::Unit> = systemd
+        .list_units()?
+        .into_iter()
+        .map(|u| (u.name.clone(), u))
+        .collect();
+
+    // Inside Flatpak reading the file would take a host process per unit, systemd can
+    // say it just as well
+    let flatpak = is_flatpak();
+    let file_description = |f: &systemd::UnitFile| {
+        if !flatpak {
+            return unit_file::description(&f.path);
+        }
+        let value = systemd
+            .unit_property(&f.name, systemd::UNIT_INTERFACE, "Description")
+            .ok()?;
+        String::try_from(value).ok().filter(|d| !d.is_empty())
+    };
+
+    let mut services: Vec<Service> = files
+        .into_iter()
+        .filter(|f| f.name.ends_with(".service"))
+        .map(|f| {
+            let unit = units.get(&f.name);
+            let text = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+            // Units systemd hasn't loaded are simply stopped. Templates like getty@.service
+            // can't run themselves and aliases run under their real name, those stay empty.
+            let runnable = !f.name.contains("@.") && f.state != "alias";
+            let (active_state, sub_state, description) = match unit {
+                Some(unit) => (
+                    text(&unit.active_state),
+                    text(&unit.sub_state),
+                    text(&unit.description),
+                ),
+                None if runnable => (text("inactive"), text("dead"), file_description(&f)),
+                None => (None, None, file_description(&f)),
+            };
+            Service {
+                masked: f.state.starts_with("masked"),
+                name: f.name,
+                state: f.state,
+                preset: None,
+                active_state,
+                sub_state,
+                description,
+            }
+        })
+        .collect();
+
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    // A unit overridden in /etc is listed once per directory, the first one wins
+    services.dedup_by(|a, b| a.name == b.name);
+    Ok(services)
+}
+
+/// Fills in what the distribution's preset files ask for. Only the service list shows
+/// this, so it isn't part of every listing.
+pub fn add_presets(services: &mut [Service], user: bool) {
+    let presets = unit_file::Presets::load(user);
+    for service in services {
+        service.preset = Some(presets.state(&service.name).to_string());
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_system_services() -> Result<Vec<Service>, Error> {
+    list_unit_files(false)
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_user_services() -> Result<Vec<Service>, Error> {
+    list_unit_files(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_user_services() -> Result<Vec<Service>, Error> {
+    Ok(Vec::new())
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_system_services() -> Result<Vec<Service>, Error> {
+    // Windows services support can be added later via 'sc' command
+    Ok(Vec::new())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn get_system_services() -> Result<Vec<Service>, Error> {
+    Ok(Vec::new())
+}
+
+// Enables or disables one unit file on an existing connection, without reloading
+#[cfg(target_os = "linux")]
+fn set_enabled(systemd: &Systemd, name: &str, enable: bool) -> Result<(), Error> {
+    let result = if enable {
+        systemd.enable_unit_files(&[name])
+    } else {
+        systemd.disable_unit_files(&[name])
+    };
+    result.map_err(|e| e.with("unit", name))
+}
+
+/// Enables or disables a system service and reloads the manager, as `systemctl enable`
+/// does. systemd asks polkit, which shows the password dialog; cancelling stops waiting
+/// for it. The `manage-unit-files` authorization implies `reload-daemon` and
+/// `manage-units`, so the reload and a following start or stop don't ask again.
+#[cfg(target_os = "linux")]
+pub fn toggle_service(name: String, enable: bool, cancel: &CancelToken) -> Result<(), Error> {
+    cancel.check()?;
+    let systemd = Systemd::connect(Bus::System)?.with_cancel(cancel);
+    set_enabled(&systemd, &name, enable)?;
+    systemd.reload()
+}
+
+#[cfg(target_os = "linux")]
+pub fn toggle_user_service(name: String, enable: bool, cancel: &CancelToken) -> Result<(), Error> {
+    // User units belong to the user, no password needed
+    cancel.check()?;
+    let systemd = Systemd::connect(Bus::User)?.with_cancel(cancel);
+    set_enabled(&systemd, &name, enable)?;
+    systemd.reload()
+}
+
+// Masks or unmasks one unit file on an existing connection, without reloading
+#[cfg(target_os = "linux")]
+fn set_masked(systemd: &Systemd, name: &str, mask: bool) -> Result<(), Error> {
+    let result = if mask {
+        systemd.mask_unit_files(&[name])
+    } else {
+        systemd.unmask_unit_files(&[name])
+    };
+    result.map_err(|e| e.with("unit", name))
+}
+
+/// Masks a system service so nothing can start it, not even another unit that
+/// depends on it, or unmasks it again. The mask only holds once the manager has
+/// reloaded, which the same password covers, see `toggle_service`.
+#[cfg(target_os = "linux")]
+pub fn mask_service(name: String, mask: bool, cancel: &CancelToken) -> Result<(), Error> {
+    cancel.check()?;
+    let systemd = Systemd::connect(Bus::System)?.with_cancel(cancel);
+    set_masked(&systemd, &name, mask)?;
+    systemd.reload()
+}
+
+#[cfg(target_os = "linux")]
+pub fn mask_user_service(name: String, mask: bool, cancel: &CancelToken) -> Result<(), Error> {
+    cancel.check()?;
+    let systemd = Systemd::connect(Bus::User)?.with_cancel(cancel);
+    set_masked(&systemd, &name, mask)?;
+    systemd.reload()
+}
+
+// How long to wait for a start or stop before reporting the state it is still in
+#[cfg(target_os = "linux")]
+const JOB_WAIT: std::time::Duration = std::time::Duration::from_secs(30);
+
+// Waits until systemd has no job queued for the unit any more, then reads where it
+// ended up. Cancelling or a slow unit only ends the wait, the job itself keeps going.
+#[cfg(target_os = "linux")]
+fn settled_state(
+    systemd: &Systemd,
+    name: &str,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    let deadline = std::time::Instant::now() + JOB_WAIT;
+    loop {
+        let properties = systemd.unit_properties(name, systemd::UNIT_INTERFACE)?;
+        let job_pending = properties
+            .get("Job")
+            .and_then(|v| v.try_clone().ok())
+            .and_then(|v| <(u32, zbus::zvariant::OwnedObjectPath)>::try_from(v).ok())
+            .is_some_and(|(id, _)| id != 0);
+        if !job_pending || cancel.is_cancelled() || std::time::Instant::now() >= deadline {
+            let text = |key: &str| {
+                properties
+                    .get(key)
+                    .and_then(|v| <&str>::try_from(v).ok())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            return Ok(RuntimeState {
+                active_state: text("ActiveState"),
+                sub_state: text("SubState"),
+            });
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn run_action(
+    bus: Bus,
+    name: &str,
+    action: ServiceAction,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    cancel.check()?;
+    let systemd = Systemd::connect(bus)?.with_cancel(cancel);
+    let method = match action {
+        ServiceAction::Start => "StartUnit",
+        ServiceAction::Stop => "StopUnit",
+        ServiceAction::Restart => "RestartUnit",
+        ServiceAction::Reload => "ReloadUnit",
+    };
+    systemd.queue_job(method, name)?;
+    settled_state(&systemd, name, cancel).map_err(|e| e.with("unit", name))
+}
+
+/// Starts, stops, restarts or reloads a system service right away, independent of
+/// whether it is enabled. Right after a toggle polkit still remembers the password,
+/// see `toggle_service`.
+#[cfg(target_os = "linux")]
+pub fn control_service(
+    name: String,
+    action: ServiceAction,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    run_action(Bus::System, &name, action, cancel)
+}
+
+#[cfg(target_os = "linux")]
+pub fn control_user_service(
+    name: String,
+    action: ServiceAction,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    run_action(Bus::User, &name, action, cancel)
+}
+
+/// Enables or disables several system or user services, then reloads the manager
+/// once. polkit remembers the authorization for a short while, and it covers the
+/// reload too, so this asks for the password once.
+/// Returns one result per requested change, in order.
+#[cfg(target_os = "linux")]
+pub fn set_services_enabled(
+    changes: &[(String, bool)],
+    user: bool,
+    cancel: &CancelToken,
+) -> Vec<Result<(), Error>> {
+    if changes.is_empty() {
+        return Vec::new();
+    }
+
+    let systemd = match Systemd::connect(bus(user)) {
+        Ok(systemd) => systemd.with_cancel(cancel),
+        Err(error) => {
+            return changes
+                .iter()
+                .map(|(name, _)| Err(error.clone().with("unit", name)))
+                .collect();
+        }
+    };
+
+    // Once cancelled or refused, the remaining changes fail with the same error
+    let mut stop: Option<Error> = None;
+    let mut results = Vec::new();
+    for (name, enable) in changes {
+        if stop.is_none() {
+            stop = cancel.check().err();
+        }
+        let result = match &stop {
+            Some(error) => Err(error.clone().with("unit", name)),
+            None => set_enabled(&systemd, name, *enable),
+        };
+        // Going on would only bring the dismissed dialog back, or be refused the same way
+        if let Err(error) = &result {
+            if matches!(
+                error.code,
+                ErrorCode::AuthCancelled | ErrorCode::PermissionDenied
+            ) {
+                stop = Some(error.clone());
+            }
+        }
+        results.push(result);
+    }
+
+    // The links are in place even if the reload fails, but systemd won't see them
+    // until the next one, so the changes that went through report its error
+    if results.iter().any(|r| r.is_ok()) {
+        if let Err(error) = systemd.reload() {
+            for ((name, _), result) in changes.iter().zip(results.iter_mut()) {
+                if result.is_ok() {
+                    *result = Err(error.clone().with("unit", name));
+                }
+            }
+        }
+    }
+    results
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn toggle_service(_name: String, _enable: bool, _cancel: &CancelToken) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Service management is currently only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn toggle_user_service(
+    _name: String,
+    _enable: bool,
+    _cancel: &CancelToken,
+) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Service management is currently only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mask_service(_name: String, _mask: bool, _cancel: &CancelToken) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Service management is currently only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mask_user_service(_name: String, _mask: bool, _cancel: &CancelToken) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Service management is currently only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn control_service(
+    _name: String,
+    _action: ServiceAction,
+    _cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    Err(Error::unsupported(
+        "Service management is currently only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn control_user_service(
+    _name: String,
+    _action: ServiceAction,
+    _cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    Err(Error::unsupported(
+        "Service management is currently only supported on Linux",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_services_enabled(
+    changes: &[(String, bool)],
+    _user: bool,
+    _cancel: &CancelToken,
+) -> Vec<Result<(), Error>> {
+    changes
+        .iter()
+        .map(|_| {
+            Err(Error::unsupported(
+                "Service management is currently only supported on Linux",
+            ))
+        })
+        .collect()
+}
@@ -1,9 +1,11 @@
+use crate::error::Error;
+use crate::process::RuntimeStatus;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use walkdir::WalkDir;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct StartupApp {
     pub id: String,
     pub name: String,
@@ -14,65 +16,62 @@ pub struct StartupApp {
     pub size: String,
     pub location: String,
     pub publisher: String,
+    #[serde(default)]
+    pub runtime: Option<RuntimeStatus>, // Only filled in where processes can be inspected
+}
+
+#[cfg(target_os = "linux")]
+fn read_desktop_entry(path: &std::path::Path) -> Option<StartupApp> {
+    let content = fs::read_to_string(path).ok()?;
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let name = extract_value(&content, "Name").unwrap_or_else(|| file_name.clone());
+    let raw_command = extract_value(&content, "Exec").unwrap_or_default();
+    let full_command = raw_command
+        .replace("env GDK_BACKEND=x11 ", "")
+        .replace("env ", "");
+
+    // Extract clean path (first part of command)
+    let clean_path = full_command
+        .split_whitespace()
+        .next()
+        .unwrap_or(&full_command)
+        .to_string();
+    let size = get_file_size(std::path::Path::new(&clean_path));
+
+    let hidden = extract_value(&content, "Hidden")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(false);
+    let x_gnome_enabled = extract_value(&content, "X-GNOME-Autostart-enabled")
+        .map(|v| v.to_lowercase() == "true")
+        .unwrap_or(true);
+
+    let enabled = !hidden && x_gnome_enabled;
+
+    Some(StartupApp {
+        id: file_name,
+        name,
+        command: clean_path, // Show clean path
+        full_command,        // Keep full command for tooltip
+        enabled,
+        path: path.to_path_buf(),
+        size,
+        location: "Startup Folder".to_string(),
+        publisher: "Linux Desktop Entry".to_string(),
+        runtime: None,
+    })
 }
 
 #[cfg(target_os = "linux")]
 pub fn get_startup_apps() -> Vec<StartupApp> {
-    let mut apps = Vec::new();
-    if let Some(config_dir) = dirs::config_dir() {
-        let autostart_dir = config_dir.join("autostart");
-        if autostart_dir.exists() {
-            for entry in WalkDir::new(&autostart_dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if entry
-                    .path()
-                    .extension()
-                    .map_or(false, |ext| ext == "desktop")
-                {
-                    if let Ok(content) = fs::read_to_string(entry.path()) {
-                        let name = extract_value(&content, "Name")
-                            .unwrap_or_else(|| entry.file_name().to_string_lossy().to_string());
-                        let raw_command = extract_value(&content, "Exec").unwrap_or_default();
-                        let full_command = raw_command
-                            .replace("env GDK_BACKEND=x11 ", "")
-                            .replace("env ", "");
-
-                        // Extract clean path (first part of command)
-                        let clean_path = full_command
-                            .split_whitespace()
-                            .next()
-                            .unwrap_or(&full_command)
-                            .to_string();
-                        let size = get_file_size(std::path::Path::new(&clean_path));
-
-                        let hidden = extract_value(&content, "Hidden")
-                            .map(|v| v.to_lowercase() == "true")
-                            .unwrap_or(false);
-                        let x_gnome_enabled = extract_value(&content, "X-GNOME-Autostart-enabled")
-                            .map(|v| v.to_lowercase() == "true")
-                            .unwrap_or(true);
-
-                        let enabled = !hidden && x_gnome_enabled;
-
-                        apps.push(StartupApp {
-                            id: entry.file_name().to_string_lossy().to_string(),
-                            name,
-                            command: clean_path, // Show clean path
-                            full_command,        // Keep full command for tooltip
-                            enabled,
-                            path: entry.path().to_path_buf(),
-                            size,
-                            location: "Startup Folder".to_string(),
-                            publisher: "Linux Desktop Entry".to_string(),
-                        });
-                    }
-                }
-            }
-        }
-    }
-    apps
+    let Some(autostart_dir) = dirs::config_dir().map(|d| d.join("autostart")) else {
+        return Vec::new();
+    };
+    WalkDir::new(&autostart_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "desktop"))
+        .filter_map(|e| read_desktop_entry(e.path()))
+        .collect()
 }
 
 #[cfg(target_os = "windows")]
@@ -124,6 +123,7 @@ pub fn get_startup_apps() -> Vec<StartupApp> {
                         size,
                         location: "Startup Folder".to_string(),
                         publisher: "Unknown".to_string(),
+                        runtime: None,
                     });
                 }
             }
@@ -162,6 +162,7 @@ pub fn get_startup_apps() -> Vec<StartupApp> {
                 size,
                 location: "Registry (HKCU)".to_string(),
                 publisher: "Unknown".to_string(),
+                runtime: None,
             });
         }
     }
@@ -197,6 +198,7 @@ pub fn get_startup_apps() -> Vec<StartupApp> {
                 size,
                 location: "Registry (HKLM)".to_string(),
                 publisher: "System".to_string(),
+                runtime: None,
             });
         }
     }
@@ -224,8 +226,7 @@ fn get_file_size(path: &std::path::Path) -> String {
     }
 }
 
-#[allow(dead_code)]
-fn extract_value(content: &str, key: &str) -> Option<String> {
+pub fn extract_value(content: &str, key: &str) -> Option<String> {
     let key_eq = format!("{}=", key);
     for line in content.lines() {
         if line.starts_with(&key_eq) {
@@ -239,13 +240,13 @@ fn extract_value(content: &str, key: &str) -> Option<String> {
 }
 
 #[cfg(target_os = "linux")]
-pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), String> {
+pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), Error> {
     // Check if it's a symlink
     let is_symlink = fs::symlink_metadata(&path)
         .map(|m| m.file_type().is_symlink())
         .unwrap_or(false);
 
-    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let content = fs::read_to_string(&path).map_err(|e| Error::io(e, &path))?;
     let mut new_lines = Vec::new();
     let mut hidden_found = false;
     let mut gnome_enabled_found = false;
@@ -273,15 +274,15 @@ pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), String> {
     // If it was a symlink, remove it first so we can write a regular file
     // This fixes "Permission denied" when trying to write to a symlink pointing to a root-owned file
     if is_symlink {
-        fs::remove_file(&path).map_err(|e| e.to_string())?;
+        fs::remove_file(&path).map_err(|e| Error::io(e, &path))?;
     }
 
-    fs::write(path, new_lines.join("\n")).map_err(|e| e.to_string())?;
+    fs::write(&path, new_lines.join("\n")).map_err(|e| Error::io(e, &path))?;
     Ok(())
 }
 
 #[cfg(target_os = "windows")]
-pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), String> {
+pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), Error> {
     let path_str = path.to_string_lossy().to_string();
 
     // Handle Registry Entries
@@ -292,7 +293,10 @@ pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), String> {
         // User asked for toggle.
         // Let's just say "Not supported for Registry yet" or implement delete/add.
         // Implementing delete/add requires remembering the command.
-        return Err("Toggling Registry apps is not supported yet. Use Delete.".to_string());
+        return Err(
+            Error::not_managed("Toggling Registry apps is not supported yet. Use Delete.")
+                .with("path", &path_str),
+        );
     }
 
     // Handle Folder Entries (Rename logic)
@@ -308,75 +312,190 @@ pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), String> {
         PathBuf::from(p)
     };
 
-    fs::rename(path, new_path).map_err(|e| e.to_string())?;
+    fs::rename(&path, new_path).map_err(|e| Error::io(e, &path))?;
     Ok(())
 }
 
 #[cfg(not(any(target_os = "linux", target_os = "windows")))]
-pub fn toggle_app(_path: PathBuf, _enable: bool) -> Result<(), String> {
-    Err("Not supported on this OS".to_string())
+pub fn toggle_app(_path: PathBuf, _enable: bool) -> Result<(), Error> {
+    Err(Error::unsupported("Not supported on this OS"))
+}
+
+/// Where `create_app` puts the entry for `name`.
+pub fn new_app_path(name: &str) -> Option<PathBuf> {
+    let safe_name = name
+        .replace(" ", "-")
+        .replace("/", "-")
+        .replace("\\", "-")
+        .to_lowercase();
+    let extension = if cfg!(target_os = "windows") {
+        "bat"
+    } else {
+        "desktop"
+    };
+    user_autostart_dir().map(|dir| dir.join(format!("{}.{}", safe_name, extension)))
+}
+
+/// Whether the entry at `path` is enabled, reading only that entry.
+/// None if there is no such entry.
+#[cfg(target_os = "linux")]
+pub fn app_enabled(path: &std::path::Path) -> Option<bool> {
+    read_desktop_entry(path).map(|app| app.enabled)
+}
+
+// Everything listed on Windows counts as enabled, see get_startup_apps
+#[cfg(target_os = "windows")]
+pub fn app_enabled(path: &std::path::Path) -> Option<bool> {
+    if is_registry_path(path) {
+        read_registry_value(path).ok().map(|_| true)
+    } else {
+        path.exists().then_some(true)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn app_enabled(_path: &std::path::Path) -> Option<bool> {
+    None
 }
 
 #[cfg(target_os = "linux")]
-pub fn create_app(name: String, command: String, description: String) -> Result<(), String> {
-    if let Some(config_dir) = dirs::config_dir() {
-        let autostart_dir = config_dir.join("autostart");
-        if !autostart_dir.exists() {
-            fs::create_dir_all(&autostart_dir).map_err(|e| e.to_string())?;
+pub fn create_app(name: String, command: String, description: String) -> Result<(), Error> {
+    if let Some(path) = new_app_path(&name) {
+        if let Some(autostart_dir) = path.parent() {
+            fs::create_dir_all(autostart_dir).map_err(|e| Error::io(e, autostart_dir))?;
         }
 
-        let safe_name = name
-            .replace(" ", "-")
-            .replace("/", "-")
-            .replace("\\", "-")
-            .to_lowercase();
-        let filename = format!("{}.desktop", safe_name);
-        let path = autostart_dir.join(filename);
-
         let content = format!(
             "[Desktop Entry]\nType=Application\nName={}\nExec={}\nComment={}\nHidden=false\nX-GNOME-Autostart-enabled=true\n",
             name, command, description
         );
 
-        fs::write(path, content).map_err(|e| e.to_string())?;
+        fs::write(&path, content).map_err(|e| Error::io(e, &path))?;
         Ok(())
     } else {
-        Err("Could not find config directory".to_string())
+        Err(Error::not_found("Could not find config directory"))
     }
 }
 
 #[cfg(target_os = "windows")]
-pub fn create_app(name: String, command: String, _description: String) -> Result<(), String> {
-    if let Some(startup_dir) =
-        dirs::data_dir().map(|d| d.join("Microsoft\\Windows\\Start Menu\\Programs\\Startup"))
-    {
-        if !startup_dir.exists() {
-            fs::create_dir_all(&startup_dir).map_err(|e| e.to_string())?;
+pub fn create_app(name: String, command: String, _description: String) -> Result<(), Error> {
+    if let Some(path) = new_app_path(&name) {
+        if let Some(startup_dir) = path.parent() {
+            fs::create_dir_all(startup_dir).map_err(|e| Error::io(e, startup_dir))?;
         }
 
-        let safe_name = name
-            .replace(" ", "-")
-            .replace("/", "-")
-            .replace("\\", "-")
-            .to_lowercase();
-        let filename = format!("{}.bat", safe_name);
-        let path = startup_dir.join(filename);
-
         let content = format!("@echo off\nstart \"\" \"{}\"", command);
 
-        fs::write(path, content).map_err(|e| e.to_string())?;
+        fs::write(&path, content).map_err(|e| Error::io(e, &path))?;
         Ok(())
     } else {
-        Err("Could not find startup directory".to_string())
+        Err(Error::not_found("Could not find startup directory"))
     }
 }
 
 #[cfg(not(any(target_os = "linux", target_os = "windows")))]
-pub fn create_app(_name: String, _command: String, _description: String) -> Result<(), String> {
-    Err("Not supported on this OS".to_string())
+pub fn create_app(_name: String, _command: String, _description: String) -> Result<(), Error> {
+    Err(Error::unsupported("Not supported on this OS"))
 }
 
-pub fn delete_app(path: PathBuf) -> Result<(), String> {
+pub fn is_registry_path(path: &std::path::Path) -> bool {
+    path.to_string_lossy().starts_with("REGISTRY::")
+}
+
+/// Folder where new startup entries are created for the current user.
+#[cfg(target_os = "windows")]
+pub fn user_autostart_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("Microsoft\\Windows\\Start Menu\\Programs\\Startup"))
+}
+
+/// Folder where new startup entries are created for the current user.
+#[cfg(not(target_os = "windows"))]
+pub fn user_autostart_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("autostart"))
+}
+
+/// Every directory `get_startup_apps` reads entries from.
+pub fn source_dirs() -> Vec<PathBuf> {
+    user_autostart_dir().into_iter().collect()
+}
+
+/// Finds the executable behind a clean command path, searching PATH for bare names.
+pub fn resolve_executable(command: &str) -> Option<PathBuf> {
+    let program = command.trim().trim_matches('"');
+    if program.is_empty() {
+        return None;
+    }
+    let candidate = std::path::Path::new(program);
+    if candidate.is_absolute() || program.contains(std::path::MAIN_SEPARATOR) {
+        return candidate.exists().then(|| candidate.to_path_buf());
+    }
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(program))
+            .find(|p| p.is_file())
+    })
+}
+
+// HKLM values need an elevated process, which the frontend can offer to restart into
+#[cfg(target_os = "windows")]
+fn registry_error(error: std::io::Error, path: &str) -> Error {
+    let error = Error::io(error, std::path::Path::new(path));
+    if error.code == crate::error::ErrorCode::PermissionDenied {
+        return error.with("elevation", "administrator");
+    }
+    error
+}
+
+#[cfg(target_os = "windows")]
+fn open_run_key(path: &std::path::Path, flags: u32) -> Result<(RegKey, String), Error> {
+    // Parse "REGISTRY::HKCU::AppName"
+    let path_str = path.to_string_lossy().to_string();
+    let parts: Vec<&str> = path_str.split("::").collect();
+    if parts.len() != 3 {
+        return Err(Error::parse("Invalid registry path format").with("path", &path_str));
+    }
+    let root = if parts[1] == "HKCU" {
+        HKEY_CURRENT_USER
+    } else {
+        HKEY_LOCAL_MACHINE
+    };
+    let key = RegKey::predef(root)
+        .open_subkey_with_flags("Software\\Microsoft\\Windows\\CurrentVersion\\Run", flags)
+        .map_err(|e| registry_error(e, &path_str))?;
+    Ok((key, parts[2].to_string()))
+}
+
+/// Reads the command stored behind a `REGISTRY::` path.
+#[cfg(target_os = "windows")]
+pub fn read_registry_value(path: &std::path::Path) -> Result<String, Error> {
+    let (key, name) = open_run_key(path, KEY_QUERY_VALUE)?;
+    key.get_value::<String, _>(&name)
+        .map_err(|e| registry_error(e, &path.to_string_lossy()))
+}
+
+/// Writes a command back behind a `REGISTRY::` path, creating the value if needed.
+#[cfg(target_os = "windows")]
+pub fn write_registry_value(path: &std::path::Path, data: &str) -> Result<(), Error> {
+    let (key, name) = open_run_key(path, KEY_SET_VALUE)?;
+    key.set_value(&name, &data.to_string())
+        .map_err(|e| registry_error(e, &path.to_string_lossy()))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn read_registry_value(_path: &std::path::Path) -> Result<String, Error> {
+    Err(Error::unsupported(
+        "Registry entries are only available on Windows",
+    ))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn write_registry_value(_path: &std::path::Path, _data: &str) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Registry entries are only available on Windows",
+    ))
+}
+
+pub fn delete_app(path: PathBuf) -> Result<(), Error> {
     #[cfg(target_os = "windows")]
     {
         let path_str = path.to_string_lossy().to_string();
@@ -396,31 +515,20 @@ pub fn delete_app(path: PathBuf) -> Result<(), String> {
 
                 // Use open_subkey_with_flags instead of create_subkey for better control and intent
                 // KEY_SET_VALUE is required to delete values
-                let key = hk.open_subkey_with_flags("Software\\Microsoft\\Windows\\CurrentVersion\\Run", KEY_SET_VALUE | KEY_QUERY_VALUE)
-                    .map_err(|e| {
-                        if e.kind() == std::io::ErrorKind::PermissionDenied {
-                            return "Access Denied: Please run the app as Administrator to delete system items.".to_string();
-                        }
-                        e.to_string()
-                    })?;
-
-                key.delete_value(name).map_err(|e| {
-                    if e.kind() == std::io::ErrorKind::PermissionDenied {
-                        return "Access Denied: Please run the app as Administrator to delete system items.".to_string();
-                    }
-                    e.to_string()
-                })?;
+                let key = hk
+                    .open_subkey_with_flags(
+                        "Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+                        KEY_SET_VALUE | KEY_QUERY_VALUE,
+                    )
+                    .map_err(|e| registry_error(e, &path_str))?;
+
+                key.delete_value(name)
+                    .map_err(|e| registry_error(e, &path_str))?;
                 return Ok(());
             }
-            return Err("Invalid registry path format".to_string());
+            return Err(Error::parse("Invalid registry path format").with("path", &path_str));
         }
     }
 
-    fs::remove_file(path).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::PermissionDenied {
-            return "Access Denied: Please run the app as Administrator to delete this file."
-                .to_string();
-        }
-        e.to_string()
-    })
+    fs::remove_file(&path).map_err(|e| Error::io(e, &path))
 }
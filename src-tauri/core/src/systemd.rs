@@ -0,0 +1,555 @@
+use crate::cancel::CancelToken;
+use crate::error::{Error, ErrorCode};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+use zbus::blocking::{Connection, Proxy};
+use zbus::proxy::MethodFlags;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+/// Object path of the manager.
+pub const MANAGER_PATH: &str = "/org/freedesktop/systemd1";
+/// Properties of the manager itself, e.g. the boot timestamps.
+pub const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+// How often a call waiting on the password dialog checks whether it was cancelled
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+/// Properties every unit has, e.g. `ActiveState` and `Description`.
+pub const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+
+/// Which systemd instance to talk to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    System, // The system manager, changes need polkit authorization
+    User,   // The user's own manager on the session bus
+}
+
+impl Bus {
+    fn name(self) -> &'static str {
+        match self {
+            Bus::System => "system",
+            Bus::User => "user",
+        }
+    }
+}
+
+/// A unit file as returned by `ListUnitFiles`.
+#[derive(Debug, Clone)]
+pub struct UnitFile {
+    pub name: String,
+    pub path: String,
+    pub state: String, // "enabled", "disabled", "static", "masked", ...
+}
+
+/// A loaded unit as returned by `ListUnits`.
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub name: String,
+    pub description: String,
+    pub load_state: String,
+    pub active_state: String,
+    pub sub_state: String,
+    pub path: OwnedObjectPath,
+}
+
+// One ListUnits entry: name, description, load, active and sub state, following,
+// object path, job id, job type and job path
+type UnitRow = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    OwnedObjectPath,
+    u32,
+    String,
+    OwnedObjectPath,
+);
+
+/// Client for `org.freedesktop.systemd1`. Inside Flatpak the system bus is the host's,
+/// proxied for us as long as the app may talk to that name.
+pub struct Systemd {
+    connection: Connection,
+    manager: Proxy<'static>,
+    bus: Bus,
+    cancel: CancelToken,
+}
+
+impl Systemd {
+    /// Connects to the system or user manager. The usual `DBUS_SYSTEM_BUS_ADDRESS` and
+    /// `DBUS_SESSION_BUS_ADDRESS` variables point this at a mock bus instead.
+    pub fn connect(bus: Bus) -> Result<Self, Error> {
+        let connection = match bus {
+            Bus::System => Connection::system(),
+            Bus::User => Connection::session(),
+        }
+        .map_err(|e| dbus_error(e, bus))?;
+        Self::new(connection, bus)
+    }
+
+    /// Uses an existing connection, e.g. to a private bus running a mock systemd.
+    pub fn new(connection: Connection, bus: Bus) -> Result<Self, Error> {
+        let manager = Proxy::new(&connection, DESTINATION, MANAGER_PATH, MANAGER_INTERFACE)
+            .map_err(|e| dbus_error(e, bus))?;
+        Ok(Systemd {
+            connection,
+            manager,
+            bus,
+            cancel: CancelToken::new(),
+        })
+    }
+
+    /// Lets `cancel` end calls that may wait on the password dialog. The caller gets
+    /// `Cancelled` right away; systemd can't withdraw the request, so a password given
+    /// after that still goes through.
+    pub fn with_cancel(mut self, cancel: &CancelToken) -> Self {
+        self.cancel = cancel.clone();
+        self
+    }
+
+    pub fn list_unit_files(&self) -> Result<Vec<UnitFile>, Error> {
+        let files: Vec<(String, String)> = self.call("ListUnitFiles", &())?;
+        Ok(files
+            .into_iter()
+            .filter_map(|(path, state)| {
+                let name = std::path::Path::new(&path)
+                    .file_name()?
+                    .to_str()?
+                    .to_string();
+                Some(UnitFile { name, path, state })
+            })
+            .collect())
+    }
+
+    pub fn list_units(&self) -> Result<Vec<Unit>, Error> {
+        let units: Vec<UnitRow> = self.call("ListUnits", &())?;
+        Ok(units
+            .into_iter()
+            .map(|u| Unit {
+                name: u.0,
+                description: u.1,
+                load_state: u.2,
+                active_state: u.3,
+                sub_state: u.4,
+                path: u.6,
+            })
+            .collect())
+    }
+
+    /// Same value `systemctl is-enabled` prints.
+    pub fn unit_file_state(&self, name: &str) -> Result<String, Error> {
+        self.call("GetUnitFileState", &(name,))
+            .map_err(|e| e.with("unit", name))
+    }
+
+    /// Enables unit files. Call `reload` afterwards, as `systemctl enable` does.
+    pub fn enable_unit_files(&self, names: &[&str]) -> Result<(), Error> {
+        // (runtime, force), the reply lists the symlinks that changed
+        let _: (bool, Vec<(String, String, String)>) =
+            self.call_interactive("EnableUnitFiles", (owned(names), false, false))?;
+        Ok(())
+    }
+
+    /// Disables unit files. Call `reload` afterwards, as `systemctl disable` does.
+    pub fn disable_unit_files(&self, names: &[&str]) -> Result<(), Error> {
+        let _: Vec<(String, String, String)> =
+            self.call_interactive("DisableUnitFiles", (owned(names), false))?;
+        Ok(())
+    }
+
+    /// Links unit files to /dev/null so nothing can start them. Call `reload` afterwards.
+    pub fn mask_unit_files(&self, names: &[&str]) -> Result<(), Error> {
+        // (runtime, force), force replaces a unit file already in /etc
+        let _: Vec<(String, String, String)> =
+            self.call_interactive("MaskUnitFiles", (owned(names), false, false))?;
+        Ok(())
+    }
+
+    /// Removes the /dev/null links again. Call `reload` afterwards.
+    pub fn unmask_unit_files(&self, names: &[&str]) -> Result<(), Error> {
+        let _: Vec<(String, String, String)> =
+            self.call_interactive("UnmaskUnitFiles", (owned(names), false))?;
+        Ok(())
+    }
+
+    /// Queues a start, stop, restart or reload job for a unit, e.g. `method` "StartUnit".
+    /// Returns as soon as the job is queued, not when it is done.
+    pub fn queue_job(&self, method: &str, name: &str) -> Result<OwnedObjectPath, Error> {
+        // "replace" cancels a conflicting job already queued, as systemctl does
+        self.call_interactive(method, (name.to_string(), "replace"))
+            .map_err(|e| e.with("unit", name))
+    }
+
+    /// Makes the manager pick up changed unit files, `systemctl daemon-reload`.
+    pub fn reload(&self) -> Result<(), Error> {
+        self.call_interactive("Reload", ())
+    }
+
+    // Object path of a unit, loading it first if systemd hasn't yet
+    fn unit_path(&self, name: &str) -> Result<OwnedObjectPath, Error> {
+        self.call("LoadUnit", &(name,))
+            .map_err(|e| e.with("unit", name))
+    }
+
+    /// Reads one property of a unit. `interface` is e.g. `UNIT_INTERFACE` or
+    /// "org.freedesktop.systemd1.Service".
+    pub fn unit_property(
+        &self,
+        name: &str,
+        interface: &str,
+        property: &str,
+    ) -> Result<OwnedValue, Error> {
+        let unit = Proxy::new(
+            &self.connection,
+            DESTINATION,
+            self.unit_path(name)?,
+            interface.to_string(),
+        )
+        .map_err(|e| dbus_error(e, self.bus))?;
+        unit.get_property(property)
+            .map_err(|e| dbus_error(e, self.bus).with("unit", name))
+    }
+
+    /// Every property of a unit on one interface, in a single round trip.
+    pub fn unit_properties(
+        &self,
+        name: &str,
+        interface: &str,
+    ) -> Result<HashMap<String, OwnedValue>, Error> {
+        self.properties(&*self.unit_path(name)?, interface)
+            .map_err(|e| e.with("unit", name))
+    }
+
+    /// Every property of one object on one interface, e.g. of the manager at
+    /// `MANAGER_PATH` or of a unit by the path `list_units` returned.
+    pub fn properties(
+        &self,
+        path: &ObjectPath<'_>,
+        interface: &str,
+    ) -> Result<HashMap<String, OwnedValue>, Error> {
+        let properties = Proxy::new(
+            &self.connection,
+            DESTINATION,
+            path.to_owned(),
+            "org.freedesktop.DBus.Properties",
+        )
+        .map_err(|e| dbus_error(e, self.bus))?;
+        properties
+            .call("GetAll", &(interface,))
+            .map_err(|e| dbus_error(e, self.bus))
+    }
+
+    fn call<B, R>(&self, method: &str, body: &B) -> Result<R, Error>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+        R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+    {
+        self.manager
+            .call(method, body)
+            .map_err(|e| dbus_error(e, self.bus))
+    }
+
+    // Lets systemd ask polkit for a password instead of refusing outright. A dismissed
+    // dialog comes back as AuthFailed or polkit's Cancelled, a refusal as AccessDenied;
+    // with no agent to show a dialog systemd says authorization is required.
+    // The dialog can stay up for as long as the user leaves it, so the call runs on a
+    // thread of its own and is given up on as soon as `cancel` is set.
+    fn call_interactive<B, R>(&self, method: &str, body: B) -> Result<R, Error>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType + Send + 'static,
+        R: for<'d> zbus::zvariant::DynamicDeserialize<'d> + Send + 'static,
+    {
+        self.cancel.check()?;
+        let manager = self.manager.clone();
+        let name = method.to_string();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let reply = manager.call_with_flags::<_, _, R>(
+                name.as_str(),
+                MethodFlags::AllowInteractiveAuth.into(),
+                &body,
+            );
+            let _ = sender.send(reply);
+        });
+        let reply = loop {
+            match receiver.recv_timeout(CANCEL_POLL) {
+                Ok(reply) => break reply,
+                Err(RecvTimeoutError::Timeout) => self.cancel.check()?,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::failed(format!("{} was interrupted", method)))
+                }
+            }
+        };
+        reply
+            .map_err(|e| dbus_error(e, self.bus))?
+            .ok_or_else(|| Error::failed(format!("{} returned no reply", method)))
+    }
+}
+
+// Method arguments have to outlive the call, which may be given up on while it runs
+fn owned(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+/// Maps a D-Bus failure to an error code by the error name systemd or the bus replied with.
+pub fn dbus_error(error: zbus::Error, bus: Bus) -> Error {
+    let error = match error {
+        zbus::Error::MethodError(name, message, _) => {
+            let message = message.unwrap_or_else(|| name.to_string());
+            let code = match name.as_str() {
+                "org.freedesktop.DBus.Error.AccessDenied"
+                | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" => {
+                    ErrorCode::PermissionDenied
+                }
+                // What polkit reports when the password dialog was dismissed
+                "org.freedesktop.DBus.Error.AuthFailed"
+                | "org.freedesktop.PolicyKit1.Error.Cancelled" => ErrorCode::AuthCancelled,
+                "org.freedesktop.PolicyKit1.Error.NotAuthorized" => ErrorCode::PermissionDenied,
+                "org.freedesktop.systemd1.NoSuchUnit"
+                | "org.freedesktop.systemd1.LoadFailed"
+                | "org.freedesktop.DBus.Error.FileNotFound" => ErrorCode::NotFound,
+                // Nothing owns org.freedesktop.systemd1, so this isn't a systemd system
+                "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner" => ErrorCode::ToolMissing,
+                _ => ErrorCode::Failed,
+            };
+            Error::new(code, message).with("dbus_error", name.as_str())
+        }
+        // Connect failures are wrapped differently across zbus versions, look for the io error
+        e if io_error(&e).is_some_and(|io| io.kind() == std::io::ErrorKind::NotFound) => {
+            Error::new(
+                ErrorCode::ToolMissing,
+                format!("The {} bus is not available: {}", bus.name(), e),
+            )
+        }
+        e => Error::failed(e.to_string()),
+    };
+    error.with("tool", "systemd").with("bus", bus.name())
+}
+
+fn io_error(error: &zbus::Error) -> Option<&std::io::Error> {
+    let source = std::error::Error::source(error)?;
+    source.downcast_ref::<std::io::Error>().or_else(|| {
+        source
+            .downcast_ref::<Arc<std::io::Error>>()
+            .map(|e| e.as_ref())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Mutex;
+    use zbus::blocking::connection::Builder;
+
+    // Symlinks an Enable/DisableUnitFiles call changed: type, link and target
+    type Changes = Vec<(String, String, String)>;
+
+    // Just enough of systemd's manager: unit files with a state, and one unit running
+    struct MockManager {
+        files: Mutex<HashMap<String, String>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.systemd1.Manager")]
+    impl MockManager {
+        fn list_unit_files(&self) -> Vec<(String, String)> {
+            let files = self.files.lock().unwrap();
+            files
+                .iter()
+                .map(|(name, state)| (format!("/usr/lib/systemd/system/{}", name), state.clone()))
+                .collect()
+        }
+
+        fn list_units(&self) -> Vec<UnitRow> {
+            let root = OwnedObjectPath::try_from("/").unwrap();
+            vec![(
+                "sshd.service".into(),
+                "OpenSSH Daemon".into(),
+                "loaded".into(),
+                "active".into(),
+                "running".into(),
+                String::new(),
+                root.clone(),
+                0,
+                String::new(),
+                root,
+            )]
+        }
+
+        fn get_unit_file_state(&self, name: String) -> zbus::fdo::Result<String> {
+            self.files
+                .lock()
+                .unwrap()
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| zbus::fdo::Error::FileNotFound("No such file or directory".into()))
+        }
+
+        fn enable_unit_files(
+            &self,
+            names: Vec<String>,
+            _runtime: bool,
+            _force: bool,
+        ) -> zbus::fdo::Result<(bool, Changes)> {
+            self.set_state(names, "enabled")?;
+            Ok((false, Vec::new()))
+        }
+
+        fn disable_unit_files(
+            &self,
+            names: Vec<String>,
+            _runtime: bool,
+        ) -> zbus::fdo::Result<Changes> {
+            self.set_state(names, "disabled")?;
+            Ok(Vec::new())
+        }
+
+        // Stands in for a password dialog nobody answers
+        async fn reload(&self) {
+            std::future::pending::<()>().await
+        }
+    }
+
+    impl MockManager {
+        // "locked.service" is refused outright, the dialog for "dismissed.service" closed
+        fn set_state(&self, names: Vec<String>, state: &str) -> zbus::fdo::Result<()> {
+            let mut files = self.files.lock().unwrap();
+            for name in names {
+                match name.as_str() {
+                    "locked.service" => {
+                        return Err(zbus::fdo::Error::AccessDenied("Access denied".into()))
+                    }
+                    "dismissed.service" => {
+                        return Err(zbus::fdo::Error::AuthFailed("Dismissed".into()))
+                    }
+                    _ => {}
+                }
+                match files.get_mut(&name) {
+                    Some(current) => *current = state.to_string(),
+                    None => {
+                        let message = format!("Unit file {} does not exist.", name);
+                        return Err(zbus::fdo::Error::FileNotFound(message));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // Serves the mock on one end of a socket pair and connects to it from the other
+    fn mock_systemd() -> (Systemd, Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+        let files = [
+            ("sshd.service", "enabled"),
+            ("cups.service", "disabled"),
+            ("locked.service", "disabled"),
+            ("dismissed.service", "disabled"),
+            ("getty@.service", "static"),
+            ("sshd.socket", "enabled"),
+        ]
+        .into_iter()
+        .map(|(name, state)| (name.to_string(), state.to_string()))
+        .collect();
+        let server = std::thread::spawn(move || {
+            Builder::unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(
+                    MANAGER_PATH,
+                    MockManager {
+                        files: Mutex::new(files),
+                    },
+                )
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let connection = Builder::unix_stream(client).p2p().build().unwrap();
+        let server = server.join().unwrap();
+        (Systemd::new(connection, Bus::System).unwrap(), server)
+    }
+
+    #[test]
+    fn lists_unit_files_and_loaded_units() {
+        let (systemd, _server) = mock_systemd();
+        let mut files = systemd.list_unit_files().unwrap();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(files.len(), 6);
+        assert_eq!(files[0].name, "cups.service");
+        assert_eq!(files[0].path, "/usr/lib/systemd/system/cups.service");
+        assert_eq!(files[0].state, "disabled");
+
+        let units = systemd.list_units().unwrap();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].name, "sshd.service");
+        assert_eq!(units[0].active_state, "active");
+        assert_eq!(units[0].sub_state, "running");
+    }
+
+    #[test]
+    fn enables_and_disables_unit_files() {
+        let (systemd, _server) = mock_systemd();
+        systemd.enable_unit_files(&["cups.service"]).unwrap();
+        assert_eq!(systemd.unit_file_state("cups.service").unwrap(), "enabled");
+        systemd.disable_unit_files(&["cups.service"]).unwrap();
+        assert_eq!(systemd.unit_file_state("cups.service").unwrap(), "disabled");
+    }
+
+    #[test]
+    fn maps_errors_by_name() {
+        let (systemd, _server) = mock_systemd();
+        let missing = systemd.unit_file_state("nope.service").unwrap_err();
+        assert_eq!(missing.code, ErrorCode::NotFound);
+        assert_eq!(missing.context["unit"], "nope.service");
+        assert_eq!(missing.context["tool"], "systemd");
+        assert_eq!(missing.context["bus"], "system");
+
+        let missing = systemd.enable_unit_files(&["nope.service"]).unwrap_err();
+        assert_eq!(missing.code, ErrorCode::NotFound);
+
+        let refused = systemd.enable_unit_files(&["locked.service"]).unwrap_err();
+        assert_eq!(refused.code, ErrorCode::PermissionDenied);
+        assert_eq!(
+            refused.context["dbus_error"],
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+        assert_eq!(
+            systemd.unit_file_state("locked.service").unwrap(),
+            "disabled"
+        );
+
+        let dismissed = systemd
+            .enable_unit_files(&["dismissed.service"])
+            .unwrap_err();
+        assert_eq!(dismissed.code, ErrorCode::AuthCancelled);
+        assert_eq!(
+            dismissed.context["dbus_error"],
+            "org.freedesktop.DBus.Error.AuthFailed"
+        );
+    }
+
+    #[test]
+    fn gives_up_waiting_once_cancelled() {
+        let (systemd, _server) = mock_systemd();
+        let cancel = CancelToken::new();
+        let systemd = systemd.with_cancel(&cancel);
+        let started = std::time::Instant::now();
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+        let error = systemd.reload().unwrap_err();
+        assert_eq!(error.code, ErrorCode::Cancelled);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Already cancelled, nothing is sent at all
+        let error = systemd.enable_unit_files(&["cups.service"]).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Cancelled);
+        assert_eq!(systemd.unit_file_state("cups.service").unwrap(), "disabled");
+    }
+}
@@ -0,0 +1,754 @@
+use crate::error::Error;
+use crate::services;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Highest precedence first, the first directory that has the unit wins
+const SYSTEM_UNIT_DIRS: &[&str] = &[
+    "/etc/systemd/system.control",
+    "/run/systemd/system.control",
+    "/run/systemd/transient",
+    "/run/systemd/generator.early",
+    "/etc/systemd/system",
+    "/etc/systemd/system.attached",
+    "/run/systemd/system",
+    "/run/systemd/system.attached",
+    "/run/systemd/generator",
+    "/usr/local/lib/systemd/system",
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+    "/run/systemd/generator.late",
+];
+
+// Preset files, same precedence as the unit directories
+const SYSTEM_PRESET_DIRS: &[&str] = &[
+    "/etc/systemd/system-preset",
+    "/run/systemd/system-preset",
+    "/usr/local/lib/systemd/system-preset",
+    "/usr/lib/systemd/system-preset",
+    "/lib/systemd/system-preset",
+];
+
+const USER_PRESET_DIRS: &[&str] = &[
+    "/etc/systemd/user-preset",
+    "/run/systemd/user-preset",
+    "/usr/local/lib/systemd/user-preset",
+    "/usr/lib/systemd/user-preset",
+];
+
+// Settings where every assignment adds to the list instead of replacing it
+const LIST_KEYS: &[&str] = &[
+    "Documentation",
+    "Wants",
+    "Requires",
+    "Requisite",
+    "BindsTo",
+    "PartOf",
+    "Upholds",
+    "Conflicts",
+    "Before",
+    "After",
+    "OnFailure",
+    "OnSuccess",
+    "ExecCondition",
+    "ExecStartPre",
+    "ExecStart",
+    "ExecStartPost",
+    "ExecReload",
+    "ExecStop",
+    "ExecStopPost",
+    "Environment",
+    "EnvironmentFile",
+    "WantedBy",
+    "RequiredBy",
+    "UpheldBy",
+    "Alias",
+    "Also",
+];
+
+const DEPENDENCY_KEYS: &[&str] = &[
+    "Wants",
+    "Requires",
+    "Requisite",
+    "BindsTo",
+    "PartOf",
+    "Upholds",
+    "Conflicts",
+    "Before",
+    "After",
+    "OnFailure",
+    "OnSuccess",
+];
+
+const INSTALL_KEYS: &[&str] = &["WantedBy", "RequiredBy", "UpheldBy", "Alias", "Also"];
+
+/// One effective value and where it came from.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Setting {
+    pub value: String, // As written, specifiers like %i are not expanded
+    pub file: PathBuf,
+    pub line: usize, // 1-based, the first line of a continued value
+}
+
+/// A unit file merged with all of its drop-ins, the way systemd would load it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceDetails {
+    pub name: String,
+    pub fragment: PathBuf, // The unit file itself, or the template's for an instance
+    pub drop_ins: Vec<PathBuf>, // In the order they are applied
+    pub masked: bool,      // Linked to /dev/null, everything below is then empty
+    pub description: Option<Setting>,
+    pub exec_start: Vec<Setting>,
+    pub user: Option<Setting>,
+    pub restart: Option<Setting>,
+    pub dependencies: BTreeMap<String, Vec<Setting>>, // "After", "Wants", ... one unit per entry
+    pub install: BTreeMap<String, Vec<Setting>>,      // "WantedBy", "Alias", ...
+    pub sections: BTreeMap<String, BTreeMap<String, Vec<Setting>>>, // Everything else too
+}
+
+struct Assignment {
+    section: String,
+    key: String,
+    value: String,
+    line: usize,
+}
+
+/// Splits a unit file into assignments. Handles comments, sections and lines
+/// continued with a trailing backslash.
+fn parse(content: &str) -> Vec<Assignment> {
+    let mut assignments = Vec::new();
+    let mut section = String::new();
+    let mut pending: Option<(String, usize)> = None; // Continued line so far, and where it began
+
+    for (index, raw) in content.lines().enumerate() {
+        let trimmed = raw.trim();
+        // Comments may sit between continued lines without ending the value
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        let (mut line, start) = match pending.take() {
+            Some((text, start)) => (text + trimmed, start),
+            None if trimmed.is_empty() => continue,
+            None => (trimmed.to_string(), index + 1),
+        };
+        if let Some(stripped) = line.strip_suffix('\\') {
+            line = format!("{} ", stripped.trim_end());
+            pending = Some((line, start));
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            assignments.push(Assignment {
+                section: section.clone(),
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+                line: start,
+            });
+        }
+    }
+    // A backslash on the very last line continues into nothing
+    if let Some((line, start)) = pending {
+        if let Some((key, value)) = line.split_once('=') {
+            assignments.push(Assignment {
+                section,
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+                line: start,
+            });
+        }
+    }
+    assignments
+}
+
+// The sandbox doesn't see the host's /usr and /etc, so inside Flatpak the host reads
+// its files for us
+fn read_file(path: &Path) -> io::Result<String> {
+    if !services::is_flatpak() {
+        return fs::read_to_string(path);
+    }
+    let output = Command::new("flatpak-spawn")
+        .args(["--host", "cat", "--"])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::new(io::ErrorKind::NotFound, message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Names of the files in a directory, none if it doesn't exist. Same as above in Flatpak.
+fn file_names(dir: &Path) -> Vec<String> {
+    if !services::is_flatpak() {
+        return fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+    Command::new("flatpak-spawn")
+        .args(["--host", "ls", "-A", "--"])
+        .arg(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn unit_dirs(user: bool) -> Vec<PathBuf> {
+    if !user {
+        return SYSTEM_UNIT_DIRS.iter().map(PathBuf::from).collect();
+    }
+    // Home paths rather than XDG ones, Flatpak points those into the sandbox
+    let home = dirs::home_dir();
+    let mut paths = Vec::new();
+    paths.extend(home.as_ref().map(|h| h.join(".config/systemd/user")));
+    paths.push(PathBuf::from("/etc/systemd/user"));
+    paths.extend(dirs::runtime_dir().map(|r| r.join("systemd/user")));
+    paths.extend(home.map(|h| h.join(".local/share/systemd/user")));
+    paths.push(PathBuf::from("/usr/local/lib/systemd/user"));
+    paths.push(PathBuf::from("/usr/lib/systemd/user"));
+    paths
+}
+
+/// The `Description=` a unit file sets itself, without looking at drop-ins.
+pub fn description(path: &str) -> Option<String> {
+    let content = read_file(Path::new(path)).ok()?;
+    parse(&content)
+        .into_iter()
+        .rev()
+        .find(|a| a.section == "Unit" && a.key == "Description")
+        .map(|a| a.value)
+        .filter(|v| !v.is_empty())
+}
+
+// Shell-style pattern as preset files use them: `*`, `?` and `[...]`
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some(('[', rest)) if rest.contains(&']') => {
+            let Some((c, name_rest)) = name.split_first() else {
+                return false;
+            };
+            let (negated, class) = match rest.split_first() {
+                Some(('!' | '^', class)) => (true, class),
+                _ => (false, rest),
+            };
+            // A ']' right at the start belongs to the class
+            let end = 1 + class[1..].iter().position(|&x| x == ']').unwrap_or(0);
+            let (class, after) = (&class[..end], &class[end + 1..]);
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= (class[i]..=class[i + 2]).contains(c);
+                    i += 3;
+                } else {
+                    found |= class[i] == *c;
+                    i += 1;
+                }
+            }
+            found != negated && glob_match(after, name_rest)
+        }
+        Some((p, rest)) => name.first() == Some(p) && glob_match(rest, &name[1..]),
+    }
+}
+
+/// The preset rules of every preset file, in the order systemd checks them.
+pub struct Presets {
+    rules: Vec<(Vec<char>, &'static str)>, // Pattern and "enabled", "disabled" or "ignored"
+}
+
+impl Presets {
+    pub fn load(user: bool) -> Self {
+        let dirs = if user {
+            USER_PRESET_DIRS
+        } else {
+            SYSTEM_PRESET_DIRS
+        };
+        // Files apply sorted by name, one in a higher priority directory hides the same name
+        let mut by_name: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in dirs {
+            for file_name in file_names(Path::new(dir)) {
+                if file_name.ends_with(".preset") {
+                    let path = Path::new(dir).join(&file_name);
+                    by_name.entry(file_name).or_insert(path);
+                }
+            }
+        }
+
+        let mut rules = Vec::new();
+        for path in by_name.into_values().filter(|p| !is_masked(p)) {
+            // A preset linked to /dev/null reads as empty, also where is_masked can't look
+            let Ok(content) = read_file(&path) else {
+                continue;
+            };
+            for line in content.lines().map(str::trim) {
+                let mut words = line.split_whitespace();
+                let state = match words.next() {
+                    Some("enable") => "enabled",
+                    Some("disable") => "disabled",
+                    Some("ignore") => "ignored",
+                    _ => continue,
+                };
+                if let Some(pattern) = words.next() {
+                    rules.push((pattern.chars().collect(), state));
+                }
+            }
+        }
+        Presets { rules }
+    }
+
+    /// What `systemctl preset` would do with a unit. The first matching rule wins,
+    /// and a unit no rule mentions gets enabled.
+    pub fn state(&self, name: &str) -> &'static str {
+        let name: Vec<char> = name.chars().collect();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, &name))
+            .map_or("enabled", |(_, state)| state)
+    }
+}
+
+fn is_masked(path: &Path) -> bool {
+    fs::read_link(path).is_ok_and(|target| target == Path::new("/dev/null"))
+}
+
+/// Names whose drop-in directories apply to `name`, lowest priority first:
+/// "service.d", prefixes like "foo-.service.d" for "foo-bar.service", the template
+/// for an instance, then the unit itself.
+fn drop_in_names(name: &str) -> Vec<String> {
+    let Some((stem, suffix)) = name.rsplit_once('.') else {
+        return vec![name.to_string()];
+    };
+    let mut names = vec![suffix.to_string()];
+    let mut prefix = String::new();
+    let parts: Vec<&str> = stem.split('-').collect();
+    for part in &parts[..parts.len() - 1] {
+        prefix.push_str(part);
+        prefix.push('-');
+        names.push(format!("{}.{}", prefix, suffix));
+    }
+    if let Some((template, instance)) = stem.split_once('@') {
+        if !instance.is_empty() {
+            names.push(format!("{}@.{}", template, suffix));
+        }
+    }
+    names.push(name.to_string());
+    names
+}
+
+// The unit file for `name`, falling back to the template for an instance
+fn find_fragment(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let mut candidates = vec![name.to_string()];
+    if let Some((template, rest)) = name.split_once('@') {
+        if let Some((_, suffix)) = rest.rsplit_once('.') {
+            candidates.push(format!("{}@.{}", template, suffix));
+        }
+    }
+    candidates.iter().find_map(|candidate| {
+        dirs.iter()
+            .map(|d| d.join(candidate))
+            .find(|p| p.symlink_metadata().is_ok())
+    })
+}
+
+// Every "*.conf" drop-in, sorted by file name as systemd applies them. A file
+// name in a higher priority directory hides the same name further down.
+fn find_drop_ins(names: &[String], dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut by_name: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for dir in dirs {
+        for name in names {
+            let Ok(entries) = fs::read_dir(dir.join(format!("{}.d", name))) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if file_name.ends_with(".conf") {
+                    by_name.entry(file_name).or_insert_with(|| entry.path());
+                }
+            }
+        }
+    }
+    // Linking a drop-in to /dev/null switches it off
+    by_name.into_values().filter(|p| !is_masked(p)).collect()
+}
+
+fn apply(
+    sections: &mut BTreeMap<String, BTreeMap<String, Vec<Setting>>>,
+    file: &Path,
+) -> Result<(), Error> {
+    let content = read_file(file).map_err(|e| Error::io(e, file))?;
+    for assignment in parse(&content) {
+        let values = sections
+            .entry(assignment.section)
+            .or_default()
+            .entry(assignment.key.clone())
+            .or_default();
+        // An empty assignment resets the setting, e.g. "ExecStart=" before a new one
+        if assignment.value.is_empty() {
+            values.clear();
+            continue;
+        }
+        let setting = Setting {
+            value: assignment.value,
+            file: file.to_path_buf(),
+            line: assignment.line,
+        };
+        if LIST_KEYS.contains(&assignment.key.as_str()) {
+            values.push(setting);
+        } else {
+            *values = vec![setting];
+        }
+    }
+    Ok(())
+}
+
+// Dependency settings list several units per line, these get one entry each
+fn split_units(settings: &[Setting]) -> Vec<Setting> {
+    settings
+        .iter()
+        .flat_map(|s| {
+            s.value.split_whitespace().map(|unit| Setting {
+                value: unit.to_string(),
+                file: s.file.clone(),
+                line: s.line,
+            })
+        })
+        .collect()
+}
+
+fn pick(
+    sections: &BTreeMap<String, BTreeMap<String, Vec<Setting>>>,
+    section: &str,
+    keys: &[&str],
+) -> BTreeMap<String, Vec<Setting>> {
+    keys.iter()
+        .filter_map(|key| {
+            let settings = sections.get(section)?.get(*key)?;
+            (!settings.is_empty()).then(|| (key.to_string(), split_units(settings)))
+        })
+        .collect()
+}
+
+// Where a unit's configuration is read from
+struct UnitPaths {
+    fragment: PathBuf,
+    drop_ins: Vec<PathBuf>,
+    masked: bool,
+}
+
+// Asks systemd which files it loaded the unit from. It resolves aliases, templates and
+// generators itself, and inside Flatpak the unit directories can't be searched anyway.
+// None when systemd can't be asked or doesn't know a unit file for the name.
+#[cfg(target_os = "linux")]
+fn loaded_paths(name: &str, user: bool) -> Option<UnitPaths> {
+    use crate::systemd::{Bus, Systemd, UNIT_INTERFACE};
+    use zbus::zvariant::Value;
+
+    let bus = if user { Bus::User } else { Bus::System };
+    let properties = Systemd::connect(bus)
+        .ok()?
+        .unit_properties(name, UNIT_INTERFACE)
+        .ok()?;
+    let text = |key: &str| match properties.get(key).map(|v| &**v) {
+        Some(Value::Str(s)) => s.to_string(),
+        _ => String::new(),
+    };
+    let fragment = text("FragmentPath");
+    if fragment.is_empty() {
+        return None;
+    }
+    let drop_ins = match properties.get("DropInPaths").map(|v| &**v) {
+        Some(Value::Array(paths)) => paths
+            .iter()
+            .filter_map(|p| match p {
+                Value::Str(s) => Some(PathBuf::from(s.as_str())),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Some(UnitPaths {
+        fragment: PathBuf::from(fragment),
+        drop_ins,
+        masked: text("LoadState") == "masked",
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn loaded_paths(_name: &str, _user: bool) -> Option<UnitPaths> {
+    None
+}
+
+// The same by searching the unit directories the way systemd does
+fn find_paths(name: &str, user: bool) -> Result<UnitPaths, Error> {
+    let dirs = unit_dirs(user);
+    let Some(fragment) = find_fragment(name, &dirs) else {
+        return Err(Error::not_found(format!("No unit file found for {}", name)).with("unit", name));
+    };
+    if is_masked(&fragment) {
+        return Ok(UnitPaths {
+            fragment,
+            drop_ins: Vec::new(),
+            masked: true,
+        });
+    }
+
+    // An alias is a symlink to the real unit, whose drop-ins apply as well
+    let fragment = fs::canonicalize(&fragment).unwrap_or(fragment);
+    let mut names = drop_in_names(name);
+    if let Some(real) = fragment
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+    {
+        if real != name {
+            names.extend(drop_in_names(&real));
+        }
+    }
+    let drop_ins = find_drop_ins(&names, &dirs);
+    Ok(UnitPaths {
+        fragment,
+        drop_ins,
+        masked: false,
+    })
+}
+
+/// Finds a service's unit file and drop-ins, as systemd reports them or else across
+/// the systemd search paths, and merges them into the effective configuration.
+pub fn get_service_details(name: &str, user: bool) -> Result<ServiceDetails, Error> {
+    let UnitPaths {
+        fragment,
+        drop_ins,
+        masked,
+    } = match loaded_paths(name, user) {
+        Some(paths) => paths,
+        None => find_paths(name, user)?,
+    };
+    if masked {
+        return Ok(ServiceDetails {
+            name: name.to_string(),
+            fragment,
+            drop_ins: Vec::new(),
+            masked: true,
+            description: None,
+            exec_start: Vec::new(),
+            user: None,
+            restart: None,
+            dependencies: BTreeMap::new(),
+            install: BTreeMap::new(),
+            sections: BTreeMap::new(),
+        });
+    }
+
+    let mut sections = BTreeMap::new();
+    for file in std::iter::once(&fragment).chain(&drop_ins) {
+        apply(&mut sections, file)?;
+    }
+    // Drop empty leftovers of settings that were reset and never set again
+    for settings in sections.values_mut() {
+        settings.retain(|_, values: &mut Vec<Setting>| !values.is_empty());
+    }
+
+    let single = |section: &str, key: &str| -> Option<Setting> {
+        sections.get(section)?.get(key)?.last().cloned()
+    };
+    Ok(ServiceDetails {
+        name: name.to_string(),
+        description: single("Unit", "Description"),
+        exec_start: sections
+            .get("Service")
+            .and_then(|s| s.get("ExecStart"))
+            .cloned()
+            .unwrap_or_default(),
+        user: single("Service", "User"),
+        restart: single("Service", "Restart"),
+        dependencies: pick(&sections, "Unit", DEPENDENCY_KEYS),
+        install: pick(&sections, "Install", INSTALL_KEYS),
+        fragment,
+        drop_ins,
+        masked: false,
+        sections,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn values(
+        sections: &BTreeMap<String, BTreeMap<String, Vec<Setting>>>,
+        section: &str,
+        key: &str,
+    ) -> Vec<String> {
+        sections[section][key]
+            .iter()
+            .map(|s| s.value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn continued_lines_skip_comments_in_between() {
+        let content = "[Service]
+ExecStart=/usr/bin/foo \\
+# not part of the command
+    --bar \\
+; neither is this
+    --baz
+User=nobody
+";
+        let assignments = parse(content);
+        assert_eq!(assignments.len(), 2);
+        assert_eq!(assignments[0].section, "Service");
+        assert_eq!(assignments[0].key, "ExecStart");
+        assert_eq!(assignments[0].value, "/usr/bin/foo --bar --baz");
+        assert_eq!(assignments[0].line, 2);
+        assert_eq!(assignments[1].key, "User");
+        assert_eq!(assignments[1].line, 7);
+    }
+
+    #[test]
+    fn backslash_on_the_last_line_keeps_the_value() {
+        let assignments = parse("[Unit]\nDescription=Trailing \\");
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].value, "Trailing");
+    }
+
+    #[test]
+    fn drop_ins_append_lists_and_replace_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let fragment = dir.path().join("foo.service");
+        let drop_in = dir.path().join("foo.service.d/override.conf");
+        write(
+            &fragment,
+            "[Unit]\nDescription=Base\nAfter=network.target\n\
+             [Service]\nExecStart=/usr/bin/base\nExecStartPre=/usr/bin/check\n",
+        );
+        write(
+            &drop_in,
+            "[Unit]\nDescription=Override\nAfter=sound.target\n\
+             [Service]\nExecStart=\nExecStart=/usr/bin/override\nExecStartPre=\n",
+        );
+
+        let mut sections = BTreeMap::new();
+        apply(&mut sections, &fragment).unwrap();
+        apply(&mut sections, &drop_in).unwrap();
+
+        assert_eq!(
+            values(&sections, "Unit", "After"),
+            ["network.target", "sound.target"]
+        );
+        assert_eq!(values(&sections, "Unit", "Description"), ["Override"]);
+        // The empty assignment drops the fragment's ExecStart before the new one is added
+        let exec_start = &sections["Service"]["ExecStart"];
+        assert_eq!(exec_start.len(), 1);
+        assert_eq!(exec_start[0].value, "/usr/bin/override");
+        assert_eq!(exec_start[0].file, drop_in);
+        assert_eq!(exec_start[0].line, 6);
+        assert!(sections["Service"]["ExecStartPre"].is_empty());
+    }
+
+    #[test]
+    fn drop_in_names_include_prefixes_and_the_template() {
+        assert_eq!(
+            drop_in_names("foo-bar-baz.service"),
+            [
+                "service",
+                "foo-.service",
+                "foo-bar-.service",
+                "foo-bar-baz.service"
+            ]
+        );
+        assert_eq!(
+            drop_in_names("user-runtime-dir@1000.service"),
+            [
+                "service",
+                "user-.service",
+                "user-runtime-.service",
+                "user-runtime-dir@.service",
+                "user-runtime-dir@1000.service",
+            ]
+        );
+        // The template itself has no instance to fall back from
+        assert_eq!(
+            drop_in_names("getty@.service"),
+            ["service", "getty@.service"]
+        );
+    }
+
+    #[test]
+    fn instances_fall_back_to_their_template() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = [root.path().join("etc"), root.path().join("usr")];
+        write(&dirs[1].join("getty@.service"), "[Unit]\n");
+        write(&dirs[0].join("getty@tty2.service"), "[Unit]\n");
+        write(&dirs[0].join("sshd.service"), "[Unit]\n");
+        write(&dirs[1].join("sshd.service"), "[Unit]\n");
+
+        assert_eq!(
+            find_fragment("getty@tty1.service", &dirs),
+            Some(dirs[1].join("getty@.service"))
+        );
+        assert_eq!(
+            find_fragment("getty@tty2.service", &dirs),
+            Some(dirs[0].join("getty@tty2.service"))
+        );
+        assert_eq!(
+            find_fragment("sshd.service", &dirs),
+            Some(dirs[0].join("sshd.service"))
+        );
+        assert_eq!(find_fragment("nope.service", &dirs), None);
+    }
+
+    #[test]
+    fn higher_drop_ins_hide_lower_ones_with_the_same_name() {
+        let root = tempfile::tempdir().unwrap();
+        let (high, low) = (root.path().join("etc"), root.path().join("usr"));
+        write(&high.join("foo-bar.service.d/10-limits.conf"), "");
+        write(&low.join("foo-bar.service.d/10-limits.conf"), "");
+        write(&low.join("foo-bar.service.d/20-env.conf"), "");
+        write(&low.join("foo-bar.service.d/README"), "");
+        write(&low.join("foo-.service.d/15-prefix.conf"), "");
+        write(&low.join("service.d/05-all.conf"), "");
+        // Linked to /dev/null, which switches off the lower one too
+        write(&low.join("foo-bar.service.d/30-off.conf"), "");
+        std::os::unix::fs::symlink("/dev/null", high.join("foo-bar.service.d/30-off.conf"))
+            .unwrap();
+
+        let found = find_drop_ins(
+            &drop_in_names("foo-bar.service"),
+            &[high.clone(), low.clone()],
+        );
+        assert_eq!(
+            found,
+            [
+                low.join("service.d/05-all.conf"),
+                high.join("foo-bar.service.d/10-limits.conf"),
+                low.join("foo-.service.d/15-prefix.conf"),
+                low.join("foo-bar.service.d/20-env.conf"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::services::{self, RuntimeState, ServiceAction};
+use crate::startup;
+use crate::storage;
+use crate::trash;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zen_core::CancelToken;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditRecord {
+    pub timestamp: u64,
+    pub action: String, // Command name, e.g. "toggle_app"
+    pub item: String,   // Path, registry id or unit name
+    pub old_state: Option<String>,
+    pub new_state: Option<String>,
+    pub privilege: String, // "user", "administrator", or "polkit" for system services
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HistoryFilter {
+    pub action: Option<String>,
+    pub item: Option<String>, // Matches any record whose item contains this text
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub success: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+fn history_file() -> Result<PathBuf, Error> {
+    Ok(storage::data_dir()?.join("history.jsonl"))
+}
+
+pub fn enabled_label(enabled: bool) -> String {
+    if enabled { "enabled" } else { "disabled" }.to_string()
+}
+
+/// Which privileges a change to this startup entry needs.
+pub fn app_privilege(path: &Path) -> &'static str {
+    if path.to_string_lossy().starts_with("REGISTRY::HKLM") {
+        "administrator"
+    } else {
+        "user"
+    }
+}
+
+fn app_state(path: &Path) -> Option<String> {
+    startup::app_enabled(path).map(enabled_label)
+}
+
+/// Appends one record to the log. Logging never fails the change it describes.
+pub fn record<T>(
+    action: &str,
+    item: &str,
+    old_state: Option<String>,
+    new_state: Option<String>,
+    privilege: &str,
+    result: &Result<T, Error>,
+) {
+    let record = AuditRecord {
+        timestamp: storage::now(),
+        action: action.to_string(),
+        item: item.to_string(),
+        old_state,
+        new_state,
+        privilege: privilege.to_string(),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(Error::to_string),
+    };
+
+    let Ok(path) = history_file() else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let Ok(line) = serde_json::to_string(&record) else {
+        return;
+    };
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+pub fn toggle_app(path: PathBuf, enable: bool) -> Result<(), Error> {
+    let old_state = app_state(&path);
+    let result = startup::toggle_app(path.clone(), enable);
+    record(
+        "toggle_app",
+        &path.to_string_lossy(),
+        old_state,
+        Some(enabled_label(enable)),
+        app_privilege(&path),
+        &result,
+    );
+    result
+}
+
+pub fn create_app(name: String, command: String, description: String) -> Result<(), Error> {
+    // Logged under the path it gets, like every other change to an entry
+    let item =
+        startup::new_app_path(&name).map_or(name.clone(), |p| p.to_string_lossy().to_string());
+    let result = startup::create_app(name, command, description);
+    record(
+        "create_app",
+        &item,
+        None,
+        Some(enabled_label(true)),
+        "user",
+        &result,
+    );
+    result
+}
+
+pub fn delete_app(path: PathBuf) -> Result<(), Error> {
+    let old_state = app_state(&path);
+    let result = trash::trash_app(path.clone()).map(|_| ());
+    record(
+        "delete_app",
+        &path.to_string_lossy(),
+        old_state,
+        Some("trashed".to_string()),
+        app_privilege(&path),
+        &result,
+    );
+    result
+}
+
+pub fn restore_app(id: String) -> Result<(), Error> {
+    let path = trash::deleted_entry(&id).map(|entry| entry.original_path);
+    let result = trash::restore_app(id.clone());
+    let (item, privilege) = match &path {
+        Ok(path) => (path.to_string_lossy().to_string(), app_privilege(path)),
+        Err(_) => (id, "user"),
+    };
+    record(
+        "restore_app",
+        &item,
+        Some("trashed".to_string()),
+        path.ok().and_then(|p| app_state(&p)),
+        privilege,
+        &result,
+    );
+    result
+}
+
+pub fn toggle_service(name: String, enable: bool, cancel: &CancelToken) -> Result<(), Error> {
+    let old_state = services::unit_file_state(&name, false);
+    let result = services::toggle_service(name.clone(), enable, cancel);
+    record(
+        "toggle_service",
+        &name,
+        old_state,
+        Some(enabled_label(enable)),
+        services::privilege_path(),
+        &result,
+    );
+    result
+}
+
+pub fn toggle_user_service(name: String, enable: bool, cancel: &CancelToken) -> Result<(), Error> {
+    let old_state = services::unit_file_state(&name, true);
+    let result = services::toggle_user_service(name.clone(), enable, cancel);
+    record(
+        "toggle_user_service",
+        &name,
+        old_state,
+        Some(enabled_label(enable)),
+        "user",
+        &result,
+    );
+    result
+}
+
+pub fn mask_service(name: String, mask: bool, cancel: &CancelToken) -> Result<(), Error> {
+    let old_state = services::unit_file_state(&name, false);
+    let action = if mask {
+        "mask_service"
+    } else {
+        "unmask_service"
+    };
+    let result = services::mask_service(name.clone(), mask, cancel);
+    // Unmasking leaves whatever state the unit file itself has
+    let new_state = if mask {
+        Some("masked".to_string())
+    } else {
+        services::unit_file_state(&name, false)
+    };
+    record(
+        action,
+        &name,
+        old_state,
+        new_state,
+        services::privilege_path(),
+        &result,
+    );
+    result
+}
+
+pub fn mask_user_service(name: String, mask: bool, cancel: &CancelToken) -> Result<(), Error> {
+    let old_state = services::unit_file_state(&name, true);
+    let action = if mask {
+        "mask_user_service"
+    } else {
+        "unmask_user_service"
+    };
+    let result = services::mask_user_service(name.clone(), mask, cancel);
+    let new_state = if mask {
+        Some("masked".to_string())
+    } else {
+        services::unit_file_state(&name, true)
+    };
+    record(action, &name, old_state, new_state, "user", &result);
+    result
+}
+
+// Records the live state before and after, e.g. "inactive" -> "active"
+fn control(
+    name: String,
+    action: ServiceAction,
+    user: bool,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    let old_state = services::active_state(&name, user);
+    let result = if user {
+        services::control_user_service(name.clone(), action, cancel)
+    } else {
+        services::control_service(name.clone(), action, cancel)
+    };
+    let scope = if user { "_user" } else { "" };
+    record(
+        &format!("{}{}_service", action.name(), scope),
+        &name,
+        old_state,
+        result.as_ref().ok().map(|s| s.active_state.clone()),
+        if user {
+            "user"
+        } else {
+            services::privilege_path()
+        },
+        &result,
+    );
+    result
+}
+
+pub fn control_service(
+    name: String,
+    action: ServiceAction,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    control(name, action, false, cancel)
+}
+
+pub fn control_user_service(
+    name: String,
+    action: ServiceAction,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Error> {
+    control(name, action, true, cancel)
+}
+
+/// What enabling or disabling with `now` did. The change stands even when starting or
+/// stopping the service afterwards failed, so that failure comes back on its own.
+#[derive(Debug, Serialize, Clone)]
+pub struct ToggleOutcome {
+    pub state: Option<RuntimeState>, // Where the service ended up, None without `now`
+    pub control_error: Option<Error>,
+}
+
+/// Enables or disables a service, and with `now` also starts or stops it.
+pub fn toggle_service_now(
+    name: String,
+    enable: bool,
+    now: bool,
+    user: bool,
+    cancel: &CancelToken,
+) -> Result<ToggleOutcome, Error> {
+    if user {
+        toggle_user_service(name.clone(), enable, cancel)?;
+    } else {
+        toggle_service(name.clone(), enable, cancel)?;
+    }
+    if !now {
+        return Ok(ToggleOutcome {
+            state: None,
+            control_error: None,
+        });
+    }
+    let result = control(name, ServiceAction::for_enabled(enable), user, cancel);
+    Ok(ToggleOutcome {
+        control_error: result.as_ref().err().cloned(),
+        state: result.ok(),
+    })
+}
+
+/// Returns matching records, newest first.
+pub fn get_history(filter: HistoryFilter) -> Result<Vec<AuditRecord>, Error> {
+    let path = history_file()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::io(e, &path)),
+    };
+
+    let mut records: Vec<AuditRecord> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+        .filter(|r| filter.action.as_ref().is_none_or(|a| &r.action == a))
+        .filter(|r| {
+            filter
+                .item
+                .as_ref()
+                .is_none_or(|i| r.item.contains(i.as_str()))
+        })
+        .filter(|r| filter.since.is_none_or(|t| r.timestamp >= t))
+        .filter(|r| filter.until.is_none_or(|t| r.timestamp <= t))
+        .filter(|r| filter.success.is_none_or(|s| r.success == s))
+        .collect();
+
+    records.reverse();
+    if let Some(limit) = filter.limit {
+        records.truncate(limit);
+    }
+    Ok(records)
+}
@@ -0,0 +1,368 @@
+#[cfg(target_os = "linux")]
+use crate::audit::{self, HistoryFilter};
+#[cfg(target_os = "linux")]
+use crate::services;
+#[cfg(target_os = "linux")]
+use crate::startup;
+#[cfg(target_os = "linux")]
+use crate::storage;
+#[cfg(target_os = "linux")]
+use crate::watcher::{self, ChangeKind};
+use crate::Error;
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::sync::{Arc, Mutex};
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use zen_core::CancelToken;
+
+// Services have no folder worth watching, so they are compared on a timer
+#[cfg(target_os = "linux")]
+const SERVICE_POLL: Duration = Duration::from_secs(30);
+// Changes Zen itself logged this recently don't need a notification
+#[cfg(target_os = "linux")]
+const OWN_CHANGE_WINDOW: u64 = 10;
+#[cfg(target_os = "linux")]
+const AUTOSTART_FILE: &str = "zen-app-manager-background.desktop";
+
+/// What the "Revert" action of a notification undoes.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+enum Revert {
+    App(PathBuf),
+    Service(String),
+    UserService(String),
+}
+
+#[cfg(target_os = "linux")]
+impl Revert {
+    fn item(&self) -> String {
+        match self {
+            Revert::App(path) => path.to_string_lossy().to_string(),
+            Revert::Service(name) | Revert::UserService(name) => name.clone(),
+        }
+    }
+
+    // Both new and re-enabled items are reverted by disabling them, nothing gets deleted
+    fn run(self) -> Result<(), Error> {
+        let cancel = CancelToken::new();
+        match self {
+            Revert::App(path) => audit::toggle_app(path, false),
+            Revert::Service(name) => audit::toggle_service(name, false, &cancel),
+            Revert::UserService(name) => audit::toggle_user_service(name, false, &cancel),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn dbus_error(error: zbus::Error) -> Error {
+    Error::failed(error.to_string()).with("bus", "session")
+}
+
+/// Sends desktop notifications through `org.freedesktop.Notifications` and remembers
+/// which change each one is about, so its actions know what to revert.
+#[cfg(target_os = "linux")]
+struct Notifier {
+    proxy: zbus::blocking::Proxy<'static>,
+    pending: Arc<Mutex<HashMap<u32, Revert>>>,
+}
+
+#[cfg(target_os = "linux")]
+impl Notifier {
+    fn new() -> Result<Self, Error> {
+        let connection = zbus::blocking::Connection::session().map_err(dbus_error)?;
+        let proxy = zbus::blocking::Proxy::new(
+            &connection,
+            "org.freedesktop.Notifications",
+            "/org/freedesktop/Notifications",
+            "org.freedesktop.Notifications",
+        )
+        .map_err(dbus_error)?;
+        Ok(Notifier {
+            proxy,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    fn notify(&self, summary: &str, body: &str, revert: Revert) -> Result<(), Error> {
+        let actions = [
+            "default", "Open Zen", "open", "Open Zen", "revert", "Disable",
+        ];
+        let hints: HashMap<&str, zbus::zvariant::Value> = HashMap::new();
+        let id: u32 = self
+            .proxy
+            .call(
+                "Notify",
+                &(
+                    "Zen App Manager",
+                    0u32,
+                    "io.github.d0ksan8.zen-app-manager",
+                    summary,
+                    body,
+                    &actions[..],
+                    hints,
+                    -1i32,
+                ),
+            )
+            .map_err(dbus_error)?;
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, revert);
+        }
+        Ok(())
+    }
+
+    /// Handles clicks on notification actions on a background thread.
+    fn listen(&self) -> Result<(), Error> {
+        let actions = self
+            .proxy
+            .receive_signal("ActionInvoked")
+            .map_err(dbus_error)?;
+        let closed = self
+            .proxy
+            .receive_signal("NotificationClosed")
+            .map_err(dbus_error)?;
+
+        let pending = self.pending.clone();
+        std::thread::spawn(move || {
+            for message in actions {
+                let Ok((id, action)) = message.body().deserialize::<(u32, String)>() else {
+                    continue;
+                };
+                let Some(revert) = pending.lock().ok().and_then(|mut p| p.remove(&id)) else {
+                    continue;
+                };
+                match action.as_str() {
+                    "revert" => {
+                        if let Err(e) = revert.run() {
+                            storage::log_error("Could not revert change", &e);
+                        }
+                    }
+                    _ => open_zen(),
+                }
+            }
+        });
+
+        // Forget notifications that were dismissed without picking an action
+        let pending = self.pending.clone();
+        std::thread::spawn(move || {
+            for message in closed {
+                if let Ok((id, _reason)) = message.body().deserialize::<(u32, u32)>() {
+                    if let Ok(mut pending) = pending.lock() {
+                        pending.remove(&id);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_zen() {
+    match std::env::current_exe() {
+        Ok(exe) => {
+            if let Err(e) = std::process::Command::new(exe).spawn() {
+                storage::log_error("Could not open Zen", &e);
+            }
+        }
+        Err(e) => storage::log_error("Could not open Zen", &e),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn changed_by_zen(item: &str) -> bool {
+    audit::get_history(HistoryFilter {
+        item: Some(item.to_string()),
+        since: Some(storage::now().saturating_sub(OWN_CHANGE_WINDOW)),
+        success: Some(true),
+        ..Default::default()
+    })
+    .is_ok_and(|records| !records.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+fn notify_change(notifier: &Notifier, summary: &str, body: String, revert: Revert) {
+    if changed_by_zen(&revert.item()) {
+        return;
+    }
+    if let Err(e) = notifier.notify(summary, &body, revert) {
+        storage::log_error("Could not send notification", &e);
+    }
+}
+
+// Unit-file state of every system and user service, keyed by (user, name).
+// A listing that fails keeps its previous states so nothing looks new afterwards.
+#[cfg(target_os = "linux")]
+fn service_states(previous: &HashMap<(bool, String), String>) -> HashMap<(bool, String), String> {
+    let mut states = HashMap::new();
+    for (user, list) in [
+        (false, services::get_system_services()),
+        (true, services::get_user_services()),
+    ] {
+        match list {
+            Ok(list) => states.extend(list.into_iter().map(|s| ((user, s.name), s.state))),
+            Err(_) => states.extend(
+                previous
+                    .iter()
+                    .filter(|((u, _), _)| *u == user)
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            ),
+        }
+    }
+    states
+}
+
+/// Runs without a window: watches startup folders and services and raises a notification
+/// whenever a new login item shows up or a disabled one gets enabled again. Never returns
+/// unless setting up fails.
+#[cfg(target_os = "linux")]
+pub fn run() -> Result<(), Error> {
+    let notifier = Arc::new(Notifier::new()?);
+    notifier.listen()?;
+
+    let known: Mutex<HashMap<PathBuf, bool>> = Mutex::new(
+        startup::get_startup_apps()
+            .into_iter()
+            .map(|a| (a.path, a.enabled))
+            .collect(),
+    );
+    let app_notifier = notifier.clone();
+    watcher::spawn(move |change| {
+        let Ok(mut known) = known.lock() else {
+            return;
+        };
+        let app = change.app;
+        let was_enabled = known.get(&app.path).copied();
+        match change.kind {
+            ChangeKind::Removed => {
+                known.remove(&app.path);
+                return;
+            }
+            ChangeKind::Added if app.enabled => notify_change(
+                &app_notifier,
+                "New login item",
+                format!("{} will now start when you log in.", app.name),
+                Revert::App(app.path.clone()),
+            ),
+            ChangeKind::Changed if app.enabled && was_enabled == Some(false) => notify_change(
+                &app_notifier,
+                "Login item re-enabled",
+                format!("{} was disabled but will start at login again.", app.name),
+                Revert::App(app.path.clone()),
+            ),
+            _ => {}
+        }
+        known.insert(app.path, app.enabled);
+    })?;
+
+    let mut states = service_states(&HashMap::new());
+    loop {
+        std::thread::sleep(SERVICE_POLL);
+        let current = service_states(&states);
+        for ((user, name), state) in &current {
+            // Nothing to compare against while that list has never been read
+            let listed_before = states.keys().any(|(u, _)| u == user);
+            if state != "enabled" || !listed_before {
+                continue;
+            }
+            let revert = if *user {
+                Revert::UserService(name.clone())
+            } else {
+                Revert::Service(name.clone())
+            };
+            match states.get(&(*user, name.clone())).map(String::as_str) {
+                None => notify_change(
+                    &notifier,
+                    "New service enabled",
+                    format!("{} will now start at boot.", name),
+                    revert,
+                ),
+                Some("disabled") => notify_change(
+                    &notifier,
+                    "Service re-enabled",
+                    format!("{} was disabled but will start at boot again.", name),
+                    revert,
+                ),
+                _ => {}
+            }
+        }
+        states = current;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn run() -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Background mode is currently only supported on Linux",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn autostart_path() -> Result<PathBuf, Error> {
+    startup::user_autostart_dir()
+        .map(|d| d.join(AUTOSTART_FILE))
+        .ok_or_else(|| Error::not_found("Could not find config directory"))
+}
+
+/// Whether Zen starts in background mode at login.
+#[cfg(target_os = "linux")]
+pub fn get_background_mode() -> Result<bool, Error> {
+    Ok(autostart_path()?.exists())
+}
+
+/// Adds or removes the login entry that starts Zen with `--background`.
+#[cfg(target_os = "linux")]
+pub fn set_background_mode(enabled: bool) -> Result<(), Error> {
+    let path = autostart_path()?;
+    let result = if enabled {
+        // Inside Flatpak our own binary path means nothing to the session
+        let exec = match std::env::var("FLATPAK_ID") {
+            Ok(id) if services::is_flatpak() => format!("flatpak run {} --background", id),
+            _ => {
+                let exe = std::env::current_exe()?;
+                format!("\"{}\" --background", exe.display())
+            }
+        };
+        let content = format!(
+            "[Desktop Entry]\nType=Application\nName=Zen App Manager (background)\nExec={}\nComment=Notifies you about new startup items\nNoDisplay=true\nX-GNOME-Autostart-enabled=true\n",
+            exec
+        );
+        path.parent()
+            .map(fs::create_dir_all)
+            .transpose()
+            .and_then(|_| fs::write(&path, content))
+            .map_err(|e| Error::io(e, &path))
+    } else if path.exists() {
+        fs::remove_file(&path).map_err(|e| Error::io(e, &path))
+    } else {
+        Ok(())
+    };
+
+    audit::record(
+        "set_background_mode",
+        &path.to_string_lossy(),
+        None,
+        Some(audit::enabled_label(enabled)),
+        "user",
+        &result,
+    );
+    result
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_background_mode() -> Result<bool, Error> {
+    Ok(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_background_mode(_enabled: bool) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "Background mode is currently only supported on Linux",
+    ))
+}
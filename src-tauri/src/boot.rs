@@ -0,0 +1,374 @@
+// Only Linux has systemd, elsewhere most of this goes unused
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+#[cfg(target_os = "linux")]
+use crate::services;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// Unit properties we need, all monotonic timestamps are in microseconds since boot
+const UNIT_PROPERTIES: &[&str] = &[
+    "Id",
+    "After",
+    "InactiveExitTimestampMonotonic",
+    "ActiveEnterTimestampMonotonic",
+    "ActiveExitTimestampMonotonic",
+    "InactiveEnterTimestampMonotonic",
+];
+const MANAGER_PROPERTIES: &[&str] = &[
+    "FirmwareTimestampMonotonic",
+    "LoaderTimestampMonotonic",
+    "InitRDTimestampMonotonic",
+    "UserspaceTimestampMonotonic",
+    "FinishTimestampMonotonic",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BootTimes {
+    pub firmware: Option<f64>, // Only known on EFI systems with a supporting boot loader
+    pub loader: Option<f64>,
+    pub kernel: f64,
+    pub initrd: Option<f64>,
+    pub userspace: f64,
+    pub total: f64,
+}
+
+/// Activation of one unit, in seconds since the kernel started.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnitTiming {
+    pub name: String,
+    pub activating: Option<f64>,
+    pub activated: Option<f64>,
+    pub deactivating: Option<f64>,
+    pub deactivated: Option<f64>,
+    pub time: Option<f64>, // activated - activating, what blame ranks by
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChainLink {
+    pub name: String,
+    pub activated: f64, // Seconds since the kernel started
+    pub time: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BootAnalysis {
+    pub times: BootTimes,
+    pub units: Vec<UnitTiming>, // Ordered by activation start, ready for a Gantt chart
+    pub blame: Vec<UnitTiming>, // Slowest first
+    pub target: String,
+    pub critical_chain: Vec<ChainLink>, // From the target down to the first unit
+}
+
+struct UnitRecord {
+    timing: UnitTiming,
+    after: Vec<String>,
+}
+
+fn usecs(value: Option<&String>) -> Option<f64> {
+    value
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .map(|v| v as f64 / 1_000_000.0)
+}
+
+fn unit_record(props: &HashMap<String, String>) -> Option<UnitRecord> {
+    let name = props.get("Id")?.clone();
+    let activating = usecs(props.get("InactiveExitTimestampMonotonic"));
+    let activated = usecs(props.get("ActiveEnterTimestampMonotonic"));
+    let time = activating
+        .zip(activated)
+        .map(|(start, end)| end - start)
+        .filter(|t| *t > 0.0);
+
+    Some(UnitRecord {
+        timing: UnitTiming {
+            name,
+            activating,
+            activated,
+            deactivating: usecs(props.get("ActiveExitTimestampMonotonic")),
+            deactivated: usecs(props.get("InactiveEnterTimestampMonotonic")),
+            time,
+        },
+        after: props
+            .get("After")
+            .map(|a| a.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default(),
+    })
+}
+
+/// Firmware/loader/kernel/initrd/userspace split, the same numbers `systemd-analyze time` shows.
+pub fn boot_times(props: &HashMap<String, String>) -> Result<BootTimes, Error> {
+    let finish = usecs(props.get("FinishTimestampMonotonic"))
+        .ok_or_else(|| Error::failed("Boot has not finished yet"))?;
+    let userspace_start = usecs(props.get("UserspaceTimestampMonotonic")).unwrap_or(0.0);
+    let initrd_start = usecs(props.get("InitRDTimestampMonotonic"));
+    // Firmware and loader start before the kernel, so these count backwards from it
+    let firmware_start = usecs(props.get("FirmwareTimestampMonotonic"));
+    let loader_start = usecs(props.get("LoaderTimestampMonotonic"));
+
+    let kernel = initrd_start.unwrap_or(userspace_start);
+    let firmware = firmware_start.map(|f| f - loader_start.unwrap_or(0.0));
+    Ok(BootTimes {
+        firmware,
+        loader: loader_start,
+        kernel,
+        initrd: initrd_start.map(|i| userspace_start - i),
+        userspace: finish - userspace_start,
+        total: firmware_start.unwrap_or(0.0) + finish,
+    })
+}
+
+/// Follows `After=` from the target, always taking the dependency that finished last,
+/// which is what held the target up. Mirrors `systemd-analyze critical-chain`.
+fn critical_chain(target: &str, units: &HashMap<String, UnitRecord>) -> Vec<ChainLink> {
+    let mut chain = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current = units.get(target);
+
+    while let Some(unit) = current {
+        let Some(activated) = unit.timing.activated else {
+            break;
+        };
+        if !seen.insert(unit.timing.name.clone()) {
+            break;
+        }
+        chain.push(ChainLink {
+            name: unit.timing.name.clone(),
+            activated,
+            time: unit.timing.time,
+        });
+
+        let start = unit.timing.activating.unwrap_or(activated);
+        current = unit
+            .after
+            .iter()
+            .filter_map(|dep| units.get(dep))
+            .filter(|dep| dep.timing.activated.is_some_and(|a| a <= start))
+            .max_by(|a, b| {
+                let (a, b) = (a.timing.activated, b.timing.activated);
+                a.unwrap_or(0.0).total_cmp(&b.unwrap_or(0.0))
+            });
+    }
+    chain
+}
+
+// Puts the analysis together from the manager's and every unit's properties
+fn analyze(
+    manager: &HashMap<String, String>,
+    unit_properties: &[HashMap<String, String>],
+) -> Result<BootAnalysis, Error> {
+    let times = boot_times(manager)?;
+
+    let records: HashMap<String, UnitRecord> = unit_properties
+        .iter()
+        .filter_map(unit_record)
+        .map(|r| (r.timing.name.clone(), r))
+        .collect();
+
+    let mut units: Vec<UnitTiming> = records
+        .values()
+        .filter(|r| r.timing.activating.is_some() || r.timing.activated.is_some())
+        .map(|r| r.timing.clone())
+        .collect();
+    units.sort_by(|a, b| {
+        let start = |u: &UnitTiming| u.activating.or(u.activated).unwrap_or(0.0);
+        start(a).total_cmp(&start(b))
+    });
+
+    let mut blame: Vec<UnitTiming> = units.iter().filter(|u| u.time.is_some()).cloned().collect();
+    blame.sort_by(|a, b| b.time.unwrap_or(0.0).total_cmp(&a.time.unwrap_or(0.0)));
+
+    // Servers never reach graphical.target
+    let target = ["graphical.target", "multi-user.target", "default.target"]
+        .iter()
+        .find(|t| {
+            records
+                .get(**t)
+                .is_some_and(|r| r.timing.activated.is_some())
+        })
+        .unwrap_or(&"graphical.target")
+        .to_string();
+    let critical_chain = critical_chain(&target, &records);
+
+    Ok(BootAnalysis {
+        times,
+        units,
+        blame,
+        target,
+        critical_chain,
+    })
+}
+
+/// Analyzes the current boot from the timestamps systemd keeps for the manager and each unit.
+#[cfg(target_os = "linux")]
+pub fn get_boot_analysis() -> Result<BootAnalysis, Error> {
+    analyze(
+        &services::manager_properties(MANAGER_PROPERTIES)?,
+        &services::loaded_unit_properties(UNIT_PROPERTIES)?,
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_boot_analysis() -> Result<BootAnalysis, Error> {
+    Err(Error::unsupported(
+        "Boot analysis is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `systemctl show --property ...` output, one block per unit
+    fn parse_show(output: &str) -> Vec<HashMap<String, String>> {
+        output
+            .split("\n\n")
+            .map(|block| {
+                block
+                    .lines()
+                    .filter_map(|l| l.split_once('='))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<_, _>>()
+            })
+            .filter(|props| !props.is_empty())
+            .collect()
+    }
+
+    const MANAGER: &str = "\
+FirmwareTimestampMonotonic=7500000
+LoaderTimestampMonotonic=2500000
+InitRDTimestampMonotonic=1800000
+UserspaceTimestampMonotonic=4000000
+FinishTimestampMonotonic=12000000
+";
+
+    const UNITS: &str = "\
+Id=graphical.target
+After=display-manager.service multi-user.target
+InactiveExitTimestampMonotonic=11900000
+ActiveEnterTimestampMonotonic=12000000
+ActiveExitTimestampMonotonic=0
+InactiveEnterTimestampMonotonic=0
+
+Id=display-manager.service
+After=basic.target network.service
+InactiveExitTimestampMonotonic=8000000
+ActiveEnterTimestampMonotonic=11500000
+ActiveExitTimestampMonotonic=0
+InactiveEnterTimestampMonotonic=0
+
+Id=multi-user.target
+After=basic.target
+InactiveExitTimestampMonotonic=9000000
+ActiveEnterTimestampMonotonic=9000000
+ActiveExitTimestampMonotonic=0
+InactiveEnterTimestampMonotonic=0
+
+Id=network.service
+After=basic.target
+InactiveExitTimestampMonotonic=5500000
+ActiveEnterTimestampMonotonic=7900000
+ActiveExitTimestampMonotonic=0
+InactiveEnterTimestampMonotonic=0
+
+Id=basic.target
+After=
+InactiveExitTimestampMonotonic=5000000
+ActiveEnterTimestampMonotonic=5000000
+ActiveExitTimestampMonotonic=0
+InactiveEnterTimestampMonotonic=0
+
+Id=never-started.service
+After=basic.target
+InactiveExitTimestampMonotonic=0
+ActiveEnterTimestampMonotonic=0
+ActiveExitTimestampMonotonic=0
+InactiveEnterTimestampMonotonic=0
+";
+
+    fn records() -> HashMap<String, UnitRecord> {
+        parse_show(UNITS)
+            .iter()
+            .filter_map(unit_record)
+            .map(|r| (r.timing.name.clone(), r))
+            .collect()
+    }
+
+    #[test]
+    fn splits_show_output_per_unit() {
+        let units = parse_show(UNITS);
+        assert_eq!(units.len(), 6);
+        assert_eq!(units[1]["Id"], "display-manager.service");
+        assert_eq!(units[4]["After"], "");
+    }
+
+    #[test]
+    fn splits_boot_into_phases() {
+        let times = boot_times(&parse_show(MANAGER)[0]).unwrap();
+        assert_eq!(times.firmware, Some(5.0));
+        assert_eq!(times.loader, Some(2.5));
+        assert_eq!(times.kernel, 1.8);
+        assert_eq!(times.initrd, Some(2.2));
+        assert_eq!(times.userspace, 8.0);
+        assert_eq!(times.total, 19.5);
+    }
+
+    #[test]
+    fn boot_times_without_firmware_or_initrd() {
+        let props =
+            parse_show("UserspaceTimestampMonotonic=3000000\nFinishTimestampMonotonic=9000000");
+        let times = boot_times(&props[0]).unwrap();
+        assert_eq!(times.firmware, None);
+        assert_eq!(times.initrd, None);
+        assert_eq!(times.kernel, 3.0);
+        assert_eq!(times.total, 9.0);
+    }
+
+    #[test]
+    fn unfinished_boot_is_an_error() {
+        let props = parse_show("UserspaceTimestampMonotonic=3000000\nFinishTimestampMonotonic=0");
+        assert!(boot_times(&props[0]).is_err());
+    }
+
+    #[test]
+    fn follows_the_last_finished_dependency() {
+        let chain = critical_chain("graphical.target", &records());
+        let names: Vec<&str> = chain.iter().map(|l| l.name.as_str()).collect();
+        // multi-user.target finished after display-manager.service started, so it
+        // didn't hold anything up
+        assert_eq!(
+            names,
+            [
+                "graphical.target",
+                "display-manager.service",
+                "network.service",
+                "basic.target"
+            ]
+        );
+        assert_eq!(chain[1].time, Some(3.5));
+    }
+
+    #[test]
+    fn chain_of_an_unknown_target_is_empty() {
+        assert!(critical_chain("rescue.target", &records()).is_empty());
+    }
+
+    #[test]
+    fn ranks_blame_and_skips_units_that_never_ran() {
+        let analysis = analyze(&parse_show(MANAGER)[0], &parse_show(UNITS)).unwrap();
+        assert_eq!(analysis.target, "graphical.target");
+        assert_eq!(analysis.units.len(), 5);
+        assert_eq!(analysis.units[0].name, "basic.target");
+        let blame: Vec<&str> = analysis.blame.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(
+            blame,
+            [
+                "display-manager.service",
+                "network.service",
+                "graphical.target"
+            ]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::audit::{self, AuditRecord, HistoryFilter};
+use crate::boot::{self, BootTimes};
+use crate::process;
+use crate::storage;
+use crate::timeline;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+// A slowdown has to pass both limits before we call it a regression, so jitter is ignored
+const REGRESSION_SECS: f64 = 0.5;
+const REGRESSION_RATIO: f64 = 1.2;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BootRecord {
+    pub boot_id: String,
+    pub booted_at: u64, // Unix time the kernel started
+    pub recorded_at: u64,
+    pub times: BootTimes,
+    pub units: BTreeMap<String, f64>, // Activation time of each unit
+    pub apps: BTreeMap<String, f64>,  // Seconds after login each startup entry launched
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BootSummary {
+    pub boot_id: String,
+    pub booted_at: u64,
+    pub total: f64,
+    pub userspace: f64,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingKind {
+    Unit,
+    App,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimingDelta {
+    pub kind: TimingKind,
+    pub name: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+    pub delta: Option<f64>,
+    pub regressed: bool, // Noticeably slower, and Zen changed this item in between
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BootComparison {
+    pub before: BootSummary,
+    pub after: BootSummary,
+    pub total_delta: f64,
+    pub userspace_delta: f64,
+    pub changes: Vec<AuditRecord>, // What Zen changed between the two boots
+    pub items: Vec<TimingDelta>,   // Biggest slowdowns first
+}
+
+fn boots_dir() -> Result<PathBuf, Error> {
+    storage::subdir("boots")
+}
+
+/// Identifier the kernel picks at random for every boot.
+pub fn current_boot_id() -> Result<String, Error> {
+    let path = std::path::Path::new("/proc/sys/kernel/random/boot_id");
+    fs::read_to_string(path)
+        .map(|id| id.trim().to_string())
+        .map_err(|e| Error::io(e, path))
+}
+
+fn summary(record: &BootRecord, current: Option<&str>) -> BootSummary {
+    BootSummary {
+        boot_id: record.boot_id.clone(),
+        booted_at: record.booted_at,
+        total: record.times.total,
+        userspace: record.times.userspace,
+        current: current == Some(record.boot_id.as_str()),
+    }
+}
+
+fn load_boot(boot_id: &str) -> Result<BootRecord, Error> {
+    storage::check_id(boot_id)?;
+    storage::read_json(&boots_dir()?.join(format!("{}.json", boot_id)))?.ok_or_else(|| {
+        Error::not_found(format!("No recorded boot with id {}", boot_id)).with("id", boot_id)
+    })
+}
+
+/// Stores the timings of the running boot. Called on every start, so app timings
+/// seen earlier in this boot are kept and only new ones are added.
+pub fn record_current_boot() -> Result<BootSummary, Error> {
+    let boot_id = current_boot_id()?;
+    storage::check_id(&boot_id)?;
+    let analysis = boot::get_boot_analysis()?;
+
+    let now = storage::now();
+    let uptime = process::system_uptime().unwrap_or(0.0) as u64;
+    let mut record = BootRecord {
+        boot_id: boot_id.clone(),
+        booted_at: now.saturating_sub(uptime),
+        recorded_at: now,
+        times: analysis.times,
+        units: analysis
+            .blame
+            .into_iter()
+            .filter_map(|u| u.time.map(|t| (u.name, t)))
+            .collect(),
+        apps: BTreeMap::new(),
+    };
+
+    // No session to measure from (e.g. started over SSH) still leaves the boot worth keeping
+    if let Ok(login) = timeline::get_login_timeline() {
+        record.apps = login
+            .entries
+            .into_iter()
+            .filter_map(|e| e.started.map(|s| (e.id, s)))
+            .collect();
+    }
+
+    let path = boots_dir()?.join(format!("{}.json", boot_id));
+    if let Some(previous) = storage::read_json::<BootRecord>(&path)? {
+        record.booted_at = previous.booted_at;
+        for (id, started) in previous.apps {
+            record.apps.entry(id).or_insert(started);
+        }
+    }
+    storage::write_json(&path, &record)?;
+    Ok(summary(&record, Some(&boot_id)))
+}
+
+/// Every recorded boot, newest first.
+pub fn list_boots() -> Result<Vec<BootSummary>, Error> {
+    let current = current_boot_id().ok();
+    let mut boots = Vec::new();
+    let dir = boots_dir()?;
+    for entry in fs::read_dir(&dir)
+        .map_err(|e| Error::io(e, &dir))?
+        .flatten()
+    {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            // A damaged record shouldn't hide every other boot
+            if let Ok(Some(record)) = storage::read_json::<BootRecord>(&path) {
+                boots.push(summary(&record, current.as_deref()));
+            }
+        }
+    }
+    boots.sort_by_key(|b| std::cmp::Reverse(b.booted_at));
+    Ok(boots)
+}
+
+fn is_regression(before: f64, after: f64) -> bool {
+    after - before >= REGRESSION_SECS && after >= before * REGRESSION_RATIO
+}
+
+// Audit items are unit names, entry paths, or "service:"/"app:" keys from restores and imports
+fn changed_by_zen(changes: &[AuditRecord], kind: &TimingKind, name: &str) -> bool {
+    changes.iter().any(|record| {
+        let item = ["app:", "service:", "user-service:"]
+            .iter()
+            .find_map(|prefix| record.item.strip_prefix(prefix))
+            .unwrap_or(&record.item);
+        match kind {
+            TimingKind::Unit => item == name,
+            TimingKind::App => item == name || std::path::Path::new(item).ends_with(name),
+        }
+    })
+}
+
+fn deltas(
+    kind: TimingKind,
+    before: &BTreeMap<String, f64>,
+    after: &BTreeMap<String, f64>,
+    changes: &[AuditRecord],
+) -> Vec<TimingDelta> {
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let (b, a) = (before.get(name).copied(), after.get(name).copied());
+            let delta = b.zip(a).map(|(b, a)| a - b);
+            let slower = b.zip(a).is_some_and(|(b, a)| is_regression(b, a));
+            TimingDelta {
+                regressed: slower && changed_by_zen(changes, &kind, name),
+                kind: kind.clone(),
+                name: name.clone(),
+                before: b,
+                after: a,
+                delta,
+            }
+        })
+        .collect()
+}
+
+/// Compares two recorded boots, whichever order they are given in.
+pub fn compare_boots(first: String, second: String) -> Result<BootComparison, Error> {
+    let (mut before, mut after) = (load_boot(&first)?, load_boot(&second)?);
+    if before.booted_at > after.booted_at {
+        std::mem::swap(&mut before, &mut after);
+    }
+
+    let changes = audit::get_history(HistoryFilter {
+        since: Some(before.booted_at),
+        until: Some(after.booted_at),
+        success: Some(true),
+        ..Default::default()
+    })?;
+
+    let mut items = deltas(TimingKind::Unit, &before.units, &after.units, &changes);
+    items.extend(deltas(TimingKind::App, &before.apps, &after.apps, &changes));
+    items.sort_by(|a, b| {
+        let key = |d: &TimingDelta| d.delta.unwrap_or(f64::NEG_INFINITY);
+        key(b).total_cmp(&key(a))
+    });
+
+    let current = current_boot_id().ok();
+    Ok(BootComparison {
+        total_delta: after.times.total - before.times.total,
+        userspace_delta: after.times.userspace - before.times.userspace,
+        before: summary(&before, current.as_deref()),
+        after: summary(&after, current.as_deref()),
+        changes,
+        items,
+    })
+}
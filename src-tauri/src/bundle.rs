@@ -0,0 +1,387 @@
+use crate::audit;
+use crate::services::{self, Service};
+use crate::snapshot::{self, AppState, ItemResult};
+use crate::startup::{self, StartupApp};
+use crate::storage;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zen_core::CancelToken;
+
+/// Bumped whenever the bundle layout changes in a way older versions can't read.
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleApp {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    pub full_command: String,
+    pub enabled: bool,
+    pub location: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry_value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfigBundle {
+    pub version: u32,
+    pub exported_at: u64,
+    pub apps: Vec<BundleApp>,
+    pub services: Vec<Service>,
+    #[serde(default)]
+    pub user_services: Vec<Service>, // Missing from bundles exported by older versions
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,      // No matching item here, it will be added
+    Update,      // Matched, but content or enabled state differs
+    Unchanged,   // Matched and already identical
+    Missing,     // Can't be applied on this machine
+    Unsupported, // Installed, but its state is set by the unit file and can't be toggled
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportItem {
+    // "app:<id>", "service:<name>" or "user-service:<name>", pass these to import
+    // selected items
+    pub key: String,
+    pub name: String,
+    pub action: ImportAction,
+    pub matched_by: Option<String>, // "id" or "command"
+    pub target: Option<PathBuf>,
+    pub details: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportPreview {
+    pub version: u32,
+    pub exported_at: u64,
+    pub items: Vec<ImportItem>,
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+/// Writes the current startup configuration to `path`, as TOML for `.toml` files and JSON otherwise.
+pub fn export_config(path: PathBuf) -> Result<(), Error> {
+    let apps = startup::get_startup_apps()
+        .into_iter()
+        .map(snapshot::capture_app)
+        .map(|state| BundleApp {
+            id: state.app.id,
+            name: state.app.name,
+            command: state.app.command,
+            full_command: state.app.full_command,
+            enabled: state.app.enabled,
+            location: state.app.location,
+            content: state.content,
+            registry_value: state.registry_value,
+        })
+        .collect();
+
+    let bundle = ConfigBundle {
+        version: BUNDLE_VERSION,
+        exported_at: storage::now(),
+        apps,
+        services: services::get_system_services().unwrap_or_default(),
+        user_services: services::get_user_services().unwrap_or_default(),
+    };
+
+    let content = if is_toml(&path) {
+        toml::to_string_pretty(&bundle).map_err(|e| Error::failed(e.to_string()))?
+    } else {
+        serde_json::to_string_pretty(&bundle).map_err(|e| Error::failed(e.to_string()))?
+    };
+    fs::write(&path, content).map_err(|e| Error::io(e, &path))
+}
+
+fn read_bundle(path: &Path) -> Result<ConfigBundle, Error> {
+    let content = fs::read_to_string(path).map_err(|e| Error::io(e, path))?;
+    let parsed = if is_toml(path) {
+        toml::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    };
+    let bundle: ConfigBundle = parsed.map_err(|e| Error::parse(e).with("path", path.display()))?;
+
+    if bundle.version > BUNDLE_VERSION {
+        return Err(Error::unsupported(format!(
+            "This bundle was made by a newer version of Zen (format {}, supported {})",
+            bundle.version, BUNDLE_VERSION
+        ))
+        .with("version", bundle.version));
+    }
+    Ok(bundle)
+}
+
+// Where an unmatched entry gets created on this machine
+fn new_entry_path(app: &BundleApp) -> Result<PathBuf, Error> {
+    storage::check_id(&app.id)?;
+    if app.registry_value.is_some() {
+        let hive = if app.location.contains("HKLM") {
+            "HKLM"
+        } else {
+            "HKCU"
+        };
+        return Ok(PathBuf::from(format!("REGISTRY::{}::{}", hive, app.id)));
+    }
+    startup::user_autostart_dir()
+        .map(|dir| dir.join(&app.id))
+        .ok_or_else(|| Error::not_found("Could not find startup directory"))
+}
+
+fn to_state(app: &BundleApp, path: PathBuf) -> AppState {
+    AppState {
+        app: StartupApp {
+            id: app.id.clone(),
+            name: app.name.clone(),
+            command: app.command.clone(),
+            full_command: app.full_command.clone(),
+            enabled: app.enabled,
+            path,
+            size: "Unknown".to_string(),
+            location: app.location.clone(),
+            publisher: "Unknown".to_string(),
+            runtime: None,
+        },
+        content: app.content.clone(),
+        registry_value: app.registry_value.clone(),
+    }
+}
+
+fn plan_app(app: &BundleApp, current: &[StartupApp]) -> ImportItem {
+    let matched = current
+        .iter()
+        .find(|c| c.id == app.id)
+        .map(|c| (c, "id"))
+        .or_else(|| {
+            current
+                .iter()
+                .find(|c| !app.full_command.is_empty() && c.full_command == app.full_command)
+                .map(|c| (c, "command"))
+        });
+
+    let mut item = ImportItem {
+        key: format!("app:{}", app.id),
+        name: app.name.clone(),
+        action: ImportAction::Unchanged,
+        matched_by: None,
+        target: None,
+        details: Vec::new(),
+    };
+
+    match matched {
+        Some((local, by)) => {
+            let local_state = snapshot::capture_app(local.clone());
+            if local.enabled != app.enabled {
+                item.action = ImportAction::Update;
+                item.details.push(format!(
+                    "{} -> {}",
+                    if local.enabled { "enabled" } else { "disabled" },
+                    if app.enabled { "enabled" } else { "disabled" }
+                ));
+            }
+            if (app.content.is_some() && local_state.content != app.content)
+                || (app.registry_value.is_some()
+                    && local_state.registry_value != app.registry_value)
+            {
+                item.action = ImportAction::Update;
+                item.details.push("content differs".to_string());
+            }
+            item.matched_by = Some(by.to_string());
+            item.target = Some(local.path.clone());
+        }
+        None if app.content.is_none() && app.registry_value.is_none() => {
+            item.action = ImportAction::Missing;
+            item.details
+                .push("Entry content was not exported, it can't be recreated".to_string());
+        }
+        None => match new_entry_path(app) {
+            Ok(path) => {
+                item.action = ImportAction::Create;
+                item.target = Some(path);
+            }
+            Err(e) => {
+                item.action = ImportAction::Missing;
+                item.details.push(e.message);
+            }
+        },
+    }
+
+    if startup::resolve_executable(&app.command).is_none() {
+        item.details.push(format!(
+            "Executable not found on this machine: {}",
+            app.command
+        ));
+    }
+    item
+}
+
+fn plan_service(service: &Service, current: &HashMap<String, String>, user: bool) -> ImportItem {
+    let (action, details) = match current.get(&service.name) {
+        None => (
+            ImportAction::Missing,
+            vec!["Service is not installed on this machine".to_string()],
+        ),
+        Some(state) if *state == service.state => (ImportAction::Unchanged, Vec::new()),
+        Some(state)
+            if !(Service::is_toggleable(state) && Service::is_toggleable(&service.state)) =>
+        {
+            (
+                ImportAction::Unsupported,
+                vec![format!("Can't change {} to {} here", state, service.state)],
+            )
+        }
+        Some(state) => (
+            ImportAction::Update,
+            vec![format!("{} -> {}", state, service.state)],
+        ),
+    };
+    ImportItem {
+        key: format!(
+            "{}service:{}",
+            if user { "user-" } else { "" },
+            service.name
+        ),
+        name: service.name.clone(),
+        action,
+        matched_by: current.get(&service.name).map(|_| "id".to_string()),
+        target: None,
+        details,
+    }
+}
+
+fn plan(bundle: &ConfigBundle) -> Vec<ImportItem> {
+    let current_apps = startup::get_startup_apps();
+    let mut items: Vec<ImportItem> = bundle
+        .apps
+        .iter()
+        .map(|app| plan_app(app, &current_apps))
+        .collect();
+    for (user, bundled) in [(false, &bundle.services), (true, &bundle.user_services)] {
+        if bundled.is_empty() {
+            continue;
+        }
+        let installed = if user {
+            services::get_user_services()
+        } else {
+            services::get_system_services()
+        };
+        let current: HashMap<String, String> = installed
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| (s.name, s.state))
+            .collect();
+        items.extend(bundled.iter().map(|s| plan_service(s, &current, user)));
+    }
+    items
+}
+
+/// Shows what importing a bundle would change, without touching anything.
+pub fn preview_import(path: PathBuf) -> Result<ImportPreview, Error> {
+    let bundle = read_bundle(&path)?;
+    Ok(ImportPreview {
+        version: bundle.version,
+        exported_at: bundle.exported_at,
+        items: plan(&bundle),
+    })
+}
+
+// Logs one imported item and adds its result to the list
+fn finish(
+    results: &mut Vec<ItemResult>,
+    item: ImportItem,
+    privilege: &str,
+    result: Result<(), Error>,
+) {
+    audit::record(
+        "import_config",
+        &item.key,
+        None,
+        Some(item.details.join(", ")),
+        privilege,
+        &result,
+    );
+    results.push(ItemResult {
+        key: item.key,
+        error: result.err(),
+    });
+}
+
+/// Applies a bundle. With `keys` set, only those items from `preview_import` are applied.
+/// Services are changed together, per scope, so the password is only asked once.
+pub fn import_config(
+    path: PathBuf,
+    keys: Option<Vec<String>>,
+    cancel: &CancelToken,
+) -> Result<Vec<ItemResult>, Error> {
+    let bundle = read_bundle(&path)?;
+    let mut results = Vec::new();
+    let mut service_items = Vec::new();
+
+    for item in plan(&bundle) {
+        if item.action != ImportAction::Create && item.action != ImportAction::Update {
+            continue;
+        }
+        if let Some(keys) = &keys {
+            if !keys.contains(&item.key) {
+                continue;
+            }
+        }
+        if cancel.is_cancelled() {
+            results.push(ItemResult {
+                key: item.key,
+                error: Some(Error::cancelled()),
+            });
+            continue;
+        }
+
+        if let Some(id) = item.key.strip_prefix("app:") {
+            if let (Some(app), Some(target)) =
+                (bundle.apps.iter().find(|a| a.id == id), &item.target)
+            {
+                let result = snapshot::apply_app_state(&to_state(app, target.clone()));
+                let privilege = audit::app_privilege(target);
+                finish(&mut results, item, privilege, result);
+            }
+        } else if let Some((user, name)) = snapshot::service_of(&item.key) {
+            let bundled = if user {
+                &bundle.user_services
+            } else {
+                &bundle.services
+            };
+            if let Some(service) = bundled.iter().find(|s| s.name == name) {
+                let change = (service.name.clone(), service.state == "enabled");
+                service_items.push((item, user, change));
+            }
+        }
+    }
+
+    for user in [false, true] {
+        let (scope, rest): (Vec<_>, Vec<_>) = service_items
+            .into_iter()
+            .partition(|(_, item_user, _)| *item_user == user);
+        service_items = rest;
+        let changes: Vec<(String, bool)> = scope.iter().map(|(_, _, c)| c.clone()).collect();
+        let changed = services::set_services_enabled(&changes, user, cancel);
+        for ((item, _, _), result) in scope.into_iter().zip(changed) {
+            finish(
+                &mut results,
+                item,
+                snapshot::service_privilege(user),
+                result,
+            );
+        }
+    }
+
+    Ok(results)
+}
@@ -0,0 +1,540 @@
+use crate::audit::{self, ToggleOutcome};
+use crate::process;
+use crate::services::{self, RuntimeState, Service, ServiceAction};
+use crate::startup::{self, StartupApp};
+use crate::unit_file::{self, ServiceDetails};
+use crate::Error;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use zen_core::{CancelToken, ErrorCode};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_DENIED: i32 = 4;
+
+const USAGE: &str = "Usage: zen-app-manager <command> [options]
+
+Commands:
+  list                          List startup entries
+  enable <entry>                Enable a startup entry
+  disable <entry>               Disable a startup entry
+  create <name> <command>       Create a startup entry [--description <text>]
+  delete <entry>                Move a startup entry to the trash
+  services list                 List services [--user]
+  services enable <name>        Enable a service [--user] [--now]
+  services disable <name>       Disable a service [--user] [--now]
+  services start <name>         Start a service now [--user]
+  services stop <name>          Stop a service now [--user]
+  services restart <name>       Restart a service [--user]
+  services reload <name>        Make a service reload its configuration [--user]
+  services show <name>          Show a service's unit file merged with its drop-ins [--user]
+  services mask <name>          Keep a service from being started at all [--user]
+  services unmask <name>        Allow a masked service to start again [--user]
+
+An <entry> is matched by file name, display name or path. Arguments after -- are
+never read as options.
+
+Options:
+  --json                        Print machine-readable output
+  --user                        Act on user services instead of system ones
+  --now                         Also start or stop the service when enabling or disabling
+  --background                  Run without a window and notify about new startup items,
+                                given on its own
+  -h, --help                    Show this help";
+
+/// Outcome of one CLI command, printed as text or JSON.
+enum Output {
+    Apps(Vec<StartupApp>),
+    Services(Vec<Service>),
+    Done(String),
+    Runtime(String, RuntimeState), // A change plus the state the service ended up in
+    Details(Box<ServiceDetails>),
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    success: bool,
+    error: &'a Error,
+}
+
+#[derive(Serialize)]
+struct JsonDone<'a> {
+    success: bool,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<&'a RuntimeState>,
+}
+
+struct Failure {
+    code: i32,
+    error: Error,
+}
+
+fn usage(message: &str) -> Failure {
+    Failure {
+        code: EXIT_USAGE,
+        error: Error::parse(message),
+    }
+}
+
+fn failed(error: Error) -> Failure {
+    let code = match error.code {
+        ErrorCode::NotFound => EXIT_NOT_FOUND,
+        ErrorCode::PermissionDenied | ErrorCode::AuthCancelled => EXIT_DENIED,
+        _ => EXIT_FAILED,
+    };
+    Failure { code, error }
+}
+
+/// Finds exactly one startup entry by file name, display name or path.
+fn find_app(query: &str) -> Result<StartupApp, Failure> {
+    let apps = startup::get_startup_apps();
+    // An exact path or file name wins over display names, which don't have to be unique
+    if let Some(app) = apps
+        .iter()
+        .find(|a| a.path == Path::new(query) || a.id == query)
+    {
+        return Ok(app.clone());
+    }
+
+    let mut matches: Vec<StartupApp> = apps
+        .into_iter()
+        .filter(|a| a.name.eq_ignore_ascii_case(query))
+        .collect();
+    match matches.len() {
+        0 => Err(failed(
+            Error::not_found(format!("No startup entry matches \"{}\"", query))
+                .with("query", query),
+        )),
+        1 => Ok(matches.remove(0)),
+        _ => Err(failed(
+            Error::not_found(format!(
+                "\"{}\" matches several entries, use the path instead:\n{}",
+                query,
+                matches
+                    .iter()
+                    .map(|a| format!("  {}", a.path.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ))
+            .with("query", query),
+        )),
+    }
+}
+
+// Options end at "--", everything after it is taken as it is
+fn options_end(args: &[String]) -> usize {
+    args.iter().position(|a| a == "--").unwrap_or(args.len())
+}
+
+// Returns the value following a flag like "--description", removing both
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, Failure> {
+    let end = options_end(args);
+    let Some(index) = args[..end].iter().position(|a| a == flag) else {
+        return Ok(None);
+    };
+    if index + 1 >= end {
+        return Err(usage(&format!("{} needs a value", flag)));
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let rest = args.split_off(options_end(args));
+    let before = args.len();
+    args.retain(|a| a != flag);
+    let found = args.len() != before;
+    args.extend(rest);
+    found
+}
+
+fn one_argument(args: &[String], what: &str) -> Result<String, Failure> {
+    match args {
+        [value] => Ok(value.clone()),
+        [] => Err(usage(&format!("Missing {}", what))),
+        _ => Err(usage("Too many arguments")),
+    }
+}
+
+fn control(
+    name: String,
+    action: ServiceAction,
+    user: bool,
+    cancel: &CancelToken,
+) -> Result<RuntimeState, Failure> {
+    let result = if user {
+        audit::control_user_service(name, action, cancel)
+    } else {
+        audit::control_service(name, action, cancel)
+    };
+    result.map_err(failed)
+}
+
+fn run_services(mut args: Vec<String>, user: bool, now: bool) -> Result<Output, Failure> {
+    if args.is_empty() {
+        return Err(usage("Missing services command"));
+    }
+    let command = args.remove(0);
+    match (command.as_str(), ServiceAction::parse(&command)) {
+        ("list", _) => {
+            if !args.is_empty() {
+                return Err(usage("Too many arguments"));
+            }
+            let mut list = if user {
+                services::get_user_services()
+            } else {
+                services::get_system_services()
+            }
+            .map_err(failed)?;
+            services::add_presets(&mut list, user);
+            Ok(Output::Services(list))
+        }
+        ("enable" | "disable", _) => {
+            let name = one_argument(&args, "service name")?;
+            let enable = command == "enable";
+            let outcome =
+                audit::toggle_service_now(name.clone(), enable, now, user, &CancelToken::new())
+                    .map_err(failed)?;
+            let message = format!("{}d {}", command, name);
+            match outcome {
+                // The change at boot went through, only starting or stopping didn't
+                ToggleOutcome {
+                    control_error: Some(mut error),
+                    ..
+                } => {
+                    let action = ServiceAction::for_enabled(enable).name();
+                    error.message =
+                        format!("{} but couldn't {} it: {}", message, action, error.message);
+                    Err(failed(error))
+                }
+                ToggleOutcome {
+                    state: Some(state), ..
+                } => Ok(Output::Runtime(message, state)),
+                ToggleOutcome { state: None, .. } => Ok(Output::Done(message)),
+            }
+        }
+        (_, Some(action)) => {
+            let name = one_argument(&args, "service name")?;
+            let state = control(name.clone(), action, user, &CancelToken::new())?;
+            let done = match action {
+                ServiceAction::Start => "started",
+                ServiceAction::Stop => "stopped",
+                ServiceAction::Restart => "restarted",
+                ServiceAction::Reload => "reloaded",
+            };
+            Ok(Output::Runtime(format!("{} {}", done, name), state))
+        }
+        ("show", _) => {
+            let name = one_argument(&args, "service name")?;
+            let details = unit_file::get_service_details(&name, user).map_err(failed)?;
+            Ok(Output::Details(Box::new(details)))
+        }
+        ("mask" | "unmask", _) => {
+            let name = one_argument(&args, "service name")?;
+            let mask = command == "mask";
+            let cancel = CancelToken::new();
+            let result = if user {
+                audit::mask_user_service(name.clone(), mask, &cancel)
+            } else {
+                audit::mask_service(name.clone(), mask, &cancel)
+            };
+            result.map_err(failed)?;
+            Ok(Output::Done(format!("{}ed {}", command, name)))
+        }
+        (other, None) => Err(usage(&format!("Unknown services command: {}", other))),
+    }
+}
+
+fn run_command(mut args: Vec<String>) -> Result<Output, Failure> {
+    let user = take_flag(&mut args, "--user");
+    let now = take_flag(&mut args, "--now");
+    let description = take_option(&mut args, "--description")?;
+    let end = options_end(&args);
+    if let Some(unknown) = args[..end]
+        .iter()
+        .find(|a| a.starts_with('-') && a.len() > 1)
+    {
+        return Err(usage(&format!("Unknown option: {}", unknown)));
+    }
+    if end < args.len() {
+        args.remove(end);
+    }
+    if args.is_empty() {
+        return Err(usage("Missing command"));
+    }
+    let command = args.remove(0);
+
+    match command.as_str() {
+        "list" => {
+            if !args.is_empty() {
+                return Err(usage("Too many arguments"));
+            }
+            let mut apps = startup::get_startup_apps();
+            process::annotate(&mut apps);
+            Ok(Output::Apps(apps))
+        }
+        "enable" | "disable" => {
+            let app = find_app(&one_argument(&args, "startup entry")?)?;
+            audit::toggle_app(app.path.clone(), command == "enable").map_err(failed)?;
+            Ok(Output::Done(format!("{}d {}", command, app.name)))
+        }
+        "create" => {
+            let [name, exec] = <[String; 2]>::try_from(args)
+                .map_err(|_| usage("create needs a name and a command"))?;
+            audit::create_app(name.clone(), exec, description.unwrap_or_default())
+                .map_err(failed)?;
+            Ok(Output::Done(format!("created {}", name)))
+        }
+        "delete" => {
+            let app = find_app(&one_argument(&args, "startup entry")?)?;
+            audit::delete_app(app.path.clone()).map_err(failed)?;
+            Ok(Output::Done(format!("moved {} to the trash", app.name)))
+        }
+        "services" => run_services(args, user, now),
+        other => Err(usage(&format!("Unknown command: {}", other))),
+    }
+}
+
+// Like println!, but a closed pipe (e.g. `| head`) just ends the output instead of panicking
+fn out(line: &str) {
+    let _ = writeln!(std::io::stdout().lock(), "{}", line);
+}
+
+fn print_text(output: &Output) {
+    match output {
+        Output::Apps(apps) => {
+            for app in apps {
+                let state = if app.enabled { "enabled" } else { "disabled" };
+                let running = match &app.runtime {
+                    Some(runtime) if runtime.running => "running",
+                    _ => "",
+                };
+                out(&format!(
+                    "{:<9} {:<8} {:<30} {}",
+                    state,
+                    running,
+                    app.name,
+                    app.path.display()
+                ));
+            }
+        }
+        Output::Services(services) => {
+            for service in services {
+                let active = match (&service.active_state, &service.sub_state) {
+                    (Some(active), Some(sub)) => format!("{} ({})", active, sub),
+                    (Some(active), None) => active.clone(),
+                    _ => String::new(),
+                };
+                let line = format!(
+                    "{:<15} {:<20} {:<40} {}",
+                    service.state,
+                    active,
+                    service.name,
+                    service.description.as_deref().unwrap_or_default()
+                );
+                out(line.trim_end());
+            }
+        }
+        Output::Done(message) => out(message),
+        Output::Details(details) => print_details(details),
+        Output::Runtime(message, state) => out(&format!(
+            "{}, now {} ({})",
+            message, state.active_state, state.sub_state
+        )),
+    }
+}
+
+fn print_details(details: &ServiceDetails) {
+    if details.masked {
+        out(&format!("{} is masked", details.name));
+        return;
+    }
+    for file in std::iter::once(&details.fragment).chain(&details.drop_ins) {
+        out(&format!("# {}", file.display()));
+    }
+    // Every effective value, followed by the file and line that set it
+    for (section, settings) in &details.sections {
+        out(&format!("\n[{}]", section));
+        for (key, values) in settings {
+            for value in values {
+                out(&format!(
+                    "{}={}    # {}:{}",
+                    key,
+                    value.value,
+                    value.file.display(),
+                    value.line
+                ));
+            }
+        }
+    }
+}
+
+fn print_json(output: &Output) -> serde_json::Result<()> {
+    let text = match output {
+        Output::Apps(apps) => serde_json::to_string_pretty(apps)?,
+        Output::Services(services) => serde_json::to_string_pretty(services)?,
+        Output::Done(message) => serde_json::to_string(&JsonDone {
+            success: true,
+            message,
+            state: None,
+        })?,
+        Output::Details(details) => serde_json::to_string_pretty(details)?,
+        Output::Runtime(message, state) => serde_json::to_string(&JsonDone {
+            success: true,
+            message,
+            state: Some(state),
+        })?,
+    };
+    out(&text);
+    Ok(())
+}
+
+/// Runs one CLI command from the process arguments (without the program name)
+/// and returns the exit code.
+pub fn run(mut args: Vec<String>) -> i32 {
+    let json = take_flag(&mut args, "--json");
+    // "help" only counts as the command, so it can still be an argument
+    if args.is_empty()
+        || args.first().is_some_and(|a| a == "help")
+        || args[..options_end(&args)]
+            .iter()
+            .any(|a| a == "-h" || a == "--help")
+    {
+        out(USAGE);
+        return if args.is_empty() { EXIT_USAGE } else { EXIT_OK };
+    }
+
+    match run_command(args) {
+        Ok(output) => {
+            let printed = if json {
+                print_json(&output)
+            } else {
+                print_text(&output);
+                Ok(())
+            };
+            match printed {
+                Ok(()) => EXIT_OK,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    EXIT_FAILED
+                }
+            }
+        }
+        Err(failure) => {
+            if json {
+                let error = JsonError {
+                    success: false,
+                    error: &failure.error,
+                };
+                out(&serde_json::to_string(&error).unwrap_or_default());
+            } else if failure.code == EXIT_USAGE {
+                eprintln!("{}\n\n{}", failure.error, USAGE);
+            } else {
+                eprintln!("{}", failure.error);
+            }
+            failure.code
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn usage_error(list: &[&str]) -> String {
+        match run_command(args(list)) {
+            Err(Failure {
+                code: EXIT_USAGE,
+                error,
+            }) => error.message,
+            Err(failure) => panic!("exit code {}: {}", failure.code, failure.error),
+            Ok(_) => panic!("{:?} was accepted", list),
+        }
+    }
+
+    #[test]
+    fn takes_flags_before_the_separator_only() {
+        let mut list = args(&["--user", "services", "list", "--", "--user"]);
+        assert!(take_flag(&mut list, "--user"));
+        assert_eq!(list, ["services", "list", "--", "--user"]);
+        assert!(!take_flag(&mut list, "--now"));
+    }
+
+    #[test]
+    fn takes_option_values() {
+        let mut list = args(&["create", "--description", "Syncs notes", "Notes", "notes"]);
+        assert_eq!(
+            take_option(&mut list, "--description").ok().flatten(),
+            Some("Syncs notes".to_string())
+        );
+        assert_eq!(list, ["create", "Notes", "notes"]);
+
+        // The value can't come from behind the separator
+        let mut list = args(&["create", "--description", "--", "x"]);
+        assert!(take_option(&mut list, "--description").is_err());
+    }
+
+    #[test]
+    fn checks_argument_counts() {
+        assert_eq!(
+            one_argument(&args(&["a"]), "service name").ok(),
+            Some("a".to_string())
+        );
+        assert!(one_argument(&args(&[]), "service name").is_err());
+        assert!(one_argument(&args(&["a", "b"]), "service name").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        assert_eq!(usage_error(&[]), "Missing command");
+        assert_eq!(usage_error(&["frobnicate"]), "Unknown command: frobnicate");
+        assert_eq!(usage_error(&["list", "--fast"]), "Unknown option: --fast");
+        assert_eq!(usage_error(&["list", "extra"]), "Too many arguments");
+        assert_eq!(usage_error(&["enable"]), "Missing startup entry");
+        assert_eq!(
+            usage_error(&["create", "only-a-name"]),
+            "create needs a name and a command"
+        );
+        assert_eq!(
+            usage_error(&["create", "--description"]),
+            "--description needs a value"
+        );
+        assert_eq!(usage_error(&["services"]), "Missing services command");
+        assert_eq!(
+            usage_error(&["services", "frobnicate", "x"]),
+            "Unknown services command: frobnicate"
+        );
+        assert_eq!(usage_error(&["services", "start"]), "Missing service name");
+        assert_eq!(
+            usage_error(&["services", "stop", "a", "b"]),
+            "Too many arguments"
+        );
+    }
+
+    #[test]
+    fn maps_error_codes_to_exit_codes() {
+        let code = |code: ErrorCode| failed(Error::new(code, "x")).code;
+        assert_eq!(code(ErrorCode::NotFound), EXIT_NOT_FOUND);
+        assert_eq!(code(ErrorCode::PermissionDenied), EXIT_DENIED);
+        assert_eq!(code(ErrorCode::AuthCancelled), EXIT_DENIED);
+        assert_eq!(code(ErrorCode::Failed), EXIT_FAILED);
+        assert_eq!(code(ErrorCode::ToolMissing), EXIT_FAILED);
+    }
+
+    #[test]
+    fn exits_with_usage_or_help() {
+        assert_eq!(run(args(&[])), EXIT_USAGE);
+        assert_eq!(run(args(&["--help"])), EXIT_OK);
+        assert_eq!(run(args(&["services", "-h"])), EXIT_OK);
+        assert_eq!(run(args(&["--json", "frobnicate"])), EXIT_USAGE);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::audit::{self, HistoryFilter};
+use crate::scan;
+use crate::startup::{self, StartupApp};
+use crate::storage;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use zen_core::CancelToken;
+
+// Changes kept per item, older ones are dropped
+const CHANGE_LIMIT: usize = 20;
+
+// Package databases that get rewritten whenever something is installed or upgraded
+const PACKAGE_DATABASES: [&str; 4] = [
+    "/var/lib/dpkg/status",
+    "/var/lib/rpm",
+    "/usr/lib/sysimage/rpm",
+    "/var/lib/pacman/local",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub owner: Option<u32>, // uid, not known on Windows
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FingerprintChange {
+    pub key: String,
+    pub name: String,
+    pub file: String,        // "entry" or "executable"
+    pub fields: Vec<String>, // Which of path, sha256, size, mtime and owner differ, or "missing"
+    pub before: Fingerprint,
+    pub after: Option<Fingerprint>, // None once the file is gone or can't be read
+    pub detected_at: u64,
+    pub explanation: Option<String>, // Package update or a change made in Zen
+    pub flagged: bool,               // Nothing we know of explains it
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ItemFingerprints {
+    pub key: String, // "app:<path>", same keys snapshots use
+    pub name: String,
+    pub entry: Option<Fingerprint>, // The desktop file, None for registry values
+    pub executable: Option<Fingerprint>,
+    pub checked_at: u64,
+    pub changes: Vec<FingerprintChange>, // Newest last
+}
+
+fn store_file() -> Result<PathBuf, Error> {
+    Ok(storage::data_dir()?.join("fingerprints.json"))
+}
+
+fn load_store() -> Result<BTreeMap<String, ItemFingerprints>, Error> {
+    Ok(storage::read_json(&store_file()?)?.unwrap_or_default())
+}
+
+#[cfg(unix)]
+fn owner(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.uid())
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Hashes a file and records its size, modification time and owner.
+pub fn fingerprint(path: &Path) -> Result<Fingerprint, Error> {
+    let metadata = fs::metadata(path).map_err(|e| Error::io(e, path))?;
+    let mut file = File::open(path).map_err(|e| Error::io(e, path))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| Error::io(e, path))?;
+
+    Ok(Fingerprint {
+        path: path.to_path_buf(),
+        sha256: format!("{:x}", hasher.finalize()),
+        size: metadata.len(),
+        mtime: metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        owner: owner(&metadata),
+    })
+}
+
+fn changed_fields(before: &Fingerprint, after: &Fingerprint) -> Vec<String> {
+    let mut fields = Vec::new();
+    if before.path != after.path {
+        fields.push("path");
+    }
+    if before.sha256 != after.sha256 {
+        fields.push("sha256");
+    }
+    if before.size != after.size {
+        fields.push("size");
+    }
+    if before.mtime != after.mtime {
+        fields.push("mtime");
+    }
+    if before.owner != after.owner {
+        fields.push("owner");
+    }
+    fields.into_iter().map(str::to_string).collect()
+}
+
+fn package_db_changed_since(since: u64) -> bool {
+    PACKAGE_DATABASES.iter().any(|db| {
+        fs::metadata(db)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .is_some_and(|t| t.as_secs() >= since)
+    })
+}
+
+// Why a file might legitimately have changed since the last scan
+fn explain(file: &str, app: &StartupApp, current: &Fingerprint, since: u64) -> Option<String> {
+    if file == "entry" {
+        let zen_changes = audit::get_history(HistoryFilter {
+            item: Some(app.path.to_string_lossy().to_string()),
+            since: Some(since),
+            success: Some(true),
+            ..Default::default()
+        })
+        .unwrap_or_default();
+        if let Some(change) = zen_changes.first() {
+            return Some(format!("Changed in Zen ({})", change.action));
+        }
+    }
+    if package_db_changed_since(since) && scan::owned_by_package(&current.path) == Some(true) {
+        return Some("Updated by the package manager".to_string());
+    }
+    None
+}
+
+fn compare(
+    file: &str,
+    app: &StartupApp,
+    old: Option<&ItemFingerprints>,
+    after: Option<&Fingerprint>,
+    now: u64,
+) -> Option<FingerprintChange> {
+    let old = old?;
+    let before = match file {
+        "entry" => old.entry.as_ref(),
+        _ => old.executable.as_ref(),
+    }?;
+    let fields = match after {
+        Some(after) => changed_fields(before, after),
+        // Reported once, the old fingerprint stays until the file is back
+        None if old
+            .changes
+            .iter()
+            .rev()
+            .find(|c| c.file == file)
+            .is_some_and(|c| c.after.is_none()) =>
+        {
+            return None
+        }
+        None => vec!["missing".to_string()],
+    };
+    if fields.is_empty() {
+        return None;
+    }
+    let explanation = explain(file, app, after.unwrap_or(before), old.checked_at);
+    Some(FingerprintChange {
+        key: old.key.clone(),
+        name: app.name.clone(),
+        file: file.to_string(),
+        fields,
+        before: before.clone(),
+        after: after.cloned(),
+        detected_at: now,
+        flagged: explanation.is_none(),
+        explanation,
+    })
+}
+
+/// Fingerprints every startup entry and its executable, and returns what changed since
+/// the previous scan. The first scan only records a baseline. `progress` is called with
+/// (hashed, total, item name) before each item; a cancelled scan stores nothing.
+pub fn scan_fingerprints(
+    progress: impl Fn(usize, usize, &str),
+    cancel: &CancelToken,
+) -> Result<Vec<FingerprintChange>, Error> {
+    let previous = load_store()?;
+    let now = storage::now();
+    let mut store = BTreeMap::new();
+    let mut changes = Vec::new();
+
+    let apps = startup::get_startup_apps();
+    let total = apps.len();
+    for (index, app) in apps.into_iter().enumerate() {
+        cancel.check()?;
+        progress(index, total, &app.name);
+        let key = format!("app:{}", app.path.display());
+        let entry = if startup::is_registry_path(&app.path) {
+            None
+        } else {
+            fingerprint(&app.path).ok()
+        };
+        let executable = scan::app_executable(&app).and_then(|exe| fingerprint(&exe).ok());
+
+        let old = previous.get(&key);
+        let mut history = old.map(|o| o.changes.clone()).unwrap_or_default();
+        let found: Vec<FingerprintChange> = [
+            ("entry", entry.as_ref()),
+            ("executable", executable.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(file, after)| compare(file, &app, old, after, now))
+        .collect();
+
+        history.extend(found.iter().cloned());
+        let excess = history.len().saturating_sub(CHANGE_LIMIT);
+        history.drain(..excess);
+        changes.extend(found);
+
+        store.insert(
+            key.clone(),
+            ItemFingerprints {
+                key,
+                name: app.name.clone(),
+                // A file that went missing keeps its last fingerprint to compare against
+                entry: entry.or_else(|| old.and_then(|o| o.entry.clone())),
+                executable: executable.or_else(|| old.and_then(|o| o.executable.clone())),
+                checked_at: now,
+                changes: history,
+            },
+        );
+    }
+
+    progress(total, total, "");
+    storage::write_json(&store_file()?, &store)?;
+    Ok(changes)
+}
+
+/// Stored fingerprints and change history of one startup entry.
+pub fn get_fingerprints(path: PathBuf) -> Result<Option<ItemFingerprints>, Error> {
+    let key = format!("app:{}", path.display());
+    Ok(load_store()?.remove(&key))
+}
@@ -0,0 +1,382 @@
+// Desktop entries only get launched on Linux, elsewhere some of this goes unused
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use crate::exec::split_exec;
+#[cfg(target_os = "linux")]
+use crate::services;
+#[cfg(target_os = "linux")]
+use crate::startup;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(target_os = "linux")]
+use zen_core::ErrorCode;
+
+// How long we wait for the command to finish before reporting it as still running
+const CAPTURE_WINDOW: Duration = Duration::from_secs(3);
+// Output kept per stream; anything beyond is drained but dropped
+const OUTPUT_LIMIT: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LaunchResult {
+    pub argv: Vec<String>, // What was actually run, after field codes and env were handled
+    pub working_dir: Option<PathBuf>,
+    pub in_terminal: bool,
+    pub pid: u32,
+    pub running: bool, // Still running when the capture window closed
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Expands or drops the `%` field codes a launcher would fill in.
+/// We launch without files or URLs, so those codes simply disappear.
+pub fn expand_field_codes(
+    args: Vec<String>,
+    name: &str,
+    icon: Option<&str>,
+    path: &str,
+) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => continue,
+            "%i" => {
+                if let Some(icon) = icon {
+                    expanded.push("--icon".to_string());
+                    expanded.push(icon.to_string());
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut out = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => out.push('%'),
+                Some('c') => out.push_str(name),
+                Some('k') => out.push_str(path),
+                _ => {}
+            }
+        }
+        expanded.push(out);
+    }
+    expanded
+}
+
+/// Pulls leading `env NAME=value` assignments off a command line.
+pub fn split_env(args: Vec<String>) -> (Vec<(String, String)>, Vec<String>) {
+    let mut rest = args.into_iter().peekable();
+    let mut env = Vec::new();
+
+    if rest
+        .peek()
+        .is_some_and(|a| a == "env" || a.ends_with("/env"))
+    {
+        rest.next();
+        while let Some(arg) = rest.peek() {
+            match arg.split_once('=') {
+                Some((key, value)) if !key.is_empty() && !key.starts_with('-') => {
+                    env.push((key.to_string(), value.to_string()));
+                    rest.next();
+                }
+                _ => break,
+            }
+        }
+    }
+    (env, rest.collect())
+}
+
+// Arguments each terminal wants in front of the command it should run
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("x-terminal-emulator", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("kitty", &[]),
+    ("wezterm", &["start", "--"]),
+    ("foot", &[]),
+    ("alacritty", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+// Anything not in the table, e.g. from $TERMINAL, gets the xterm convention
+fn terminal_args(terminal: &str) -> &'static [&'static str] {
+    let name = terminal.rsplit('/').next().unwrap_or(terminal);
+    TERMINALS
+        .iter()
+        .find(|(t, _)| *t == name)
+        .map_or(&["-e"], |(_, args)| args)
+}
+
+#[cfg(target_os = "linux")]
+fn find_terminal() -> Option<(String, &'static [&'static str])> {
+    std::env::var("TERMINAL")
+        .ok()
+        .into_iter()
+        .chain(TERMINALS.iter().map(|(t, _)| t.to_string()))
+        .find(|t| startup::resolve_executable(t).is_some())
+        .map(|t| {
+            let args = terminal_args(&t);
+            (t, args)
+        })
+}
+
+// Reads a pipe until it closes, keeping the first OUTPUT_LIMIT bytes
+fn capture<R: Read + Send + 'static>(pipe: Option<R>) -> Arc<Mutex<Vec<u8>>> {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    if let Some(mut pipe) = pipe {
+        let sink = buffer.clone();
+        std::thread::spawn(move || {
+            let mut chunk = [0u8; 4096];
+            while let Ok(n) = pipe.read(&mut chunk) {
+                if n == 0 {
+                    break;
+                }
+                if let Ok(mut out) = sink.lock() {
+                    let room = OUTPUT_LIMIT.saturating_sub(out.len());
+                    out.extend_from_slice(&chunk[..n.min(room)]);
+                }
+            }
+        });
+    }
+    buffer
+}
+
+fn run_and_capture(
+    mut command: Command,
+    argv: Vec<String>,
+    working_dir: Option<PathBuf>,
+    in_terminal: bool,
+) -> Result<LaunchResult, Error> {
+    let mut child: Child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            let program = argv.first().map(String::as_str).unwrap_or("");
+            Error::new(Error::from(e).code, format!("Could not start {}", program))
+                .with("program", program)
+        })?;
+
+    let pid = child.id();
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+
+    let started = Instant::now();
+    let mut exit_code = None;
+    let mut running = true;
+    while started.elapsed() < CAPTURE_WINDOW {
+        if let Some(status) = child.try_wait()? {
+            exit_code = status.code();
+            running = false;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    if running {
+        // Leave it running like a session would, but still reap it once it exits
+        std::thread::spawn(move || {
+            let _ = child.wait();
+        });
+    } else {
+        // Give the readers a moment to pick up the last output
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let text = |buffer: &Arc<Mutex<Vec<u8>>>| {
+        buffer
+            .lock()
+            .map(|b| String::from_utf8_lossy(&b).to_string())
+            .unwrap_or_default()
+    };
+
+    Ok(LaunchResult {
+        argv,
+        working_dir,
+        in_terminal,
+        pid,
+        running,
+        exit_code,
+        stdout: text(&stdout),
+        stderr: text(&stderr),
+    })
+}
+
+/// Runs a startup entry the way the session would at login and reports what happened
+/// during the first few seconds.
+#[cfg(target_os = "linux")]
+pub fn launch_app(path: PathBuf) -> Result<LaunchResult, Error> {
+    let content = fs::read_to_string(&path).map_err(|e| Error::io(e, &path))?;
+    let no_exec = || Error::parse("This entry has no Exec command").with("path", path.display());
+    let exec = startup::extract_value(&content, "Exec")
+        .filter(|e| !e.is_empty())
+        .ok_or_else(no_exec)?;
+    let name = startup::extract_value(&content, "Name").unwrap_or_default();
+    let icon = startup::extract_value(&content, "Icon");
+    let in_terminal = startup::extract_value(&content, "Terminal")
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+
+    let args = expand_field_codes(
+        split_exec(&exec),
+        &name,
+        icon.as_deref(),
+        &path.to_string_lossy(),
+    );
+    let (env, mut argv) = split_env(args);
+    if argv.is_empty() {
+        return Err(no_exec());
+    }
+
+    if in_terminal {
+        let (terminal, args) = find_terminal()
+            .ok_or_else(|| Error::new(ErrorCode::ToolMissing, "No terminal emulator found"))?;
+        let prefix = std::iter::once(terminal).chain(args.iter().map(|a| a.to_string()));
+        argv.splice(0..0, prefix);
+    }
+
+    let working_dir = startup::extract_value(&content, "Path")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(dirs::home_dir);
+
+    let command = if services::is_flatpak() {
+        // Entries belong to the host, so they have to run there
+        let mut command = Command::new("flatpak-spawn");
+        command.arg("--host");
+        for (key, value) in &env {
+            command.arg(format!("--env={}={}", key, value));
+        }
+        if let Some(dir) = &working_dir {
+            command.arg(format!("--directory={}", dir.display()));
+        }
+        command.args(&argv);
+        command
+    } else {
+        let mut command = Command::new(&argv[0]);
+        command
+            .args(&argv[1..])
+            .envs(env.iter().map(|(k, v)| (k, v)));
+        if let Some(dir) = &working_dir {
+            if !dir.is_dir() {
+                return Err(Error::not_found(format!(
+                    "Working directory does not exist: {}",
+                    dir.display()
+                ))
+                .with("path", dir.display()));
+            }
+            command.current_dir(dir);
+        }
+        command
+    };
+
+    run_and_capture(command, argv, working_dir, in_terminal)
+}
+
+#[cfg(target_os = "windows")]
+pub fn launch_app(path: PathBuf) -> Result<LaunchResult, Error> {
+    let app = crate::startup::get_startup_apps()
+        .into_iter()
+        .find(|a| a.path == path)
+        .ok_or_else(|| Error::not_found("Startup entry not found").with("path", path.display()))?;
+
+    // Folder entries and Run values are both started through the shell at login
+    let argv = vec!["cmd".to_string(), "/C".to_string(), app.full_command];
+    let mut command = Command::new(&argv[0]);
+    command.args(&argv[1..]);
+    run_and_capture(command, argv, None, false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn launch_app(_path: PathBuf) -> Result<LaunchResult, Error> {
+    Err(Error::unsupported("Not supported on this OS"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            split_exec(r#""/opt/My App/app" --title "Hello \"World\"""#),
+            ["/opt/My App/app", "--title", r#"Hello "World""#]
+        );
+    }
+
+    #[test]
+    fn drops_file_and_url_codes() {
+        let args = strings(&["app", "%f", "%u", "--open", "%F", "%U"]);
+        assert_eq!(
+            expand_field_codes(args, "App", None, "/a.desktop"),
+            ["app", "--open"]
+        );
+    }
+
+    #[test]
+    fn expands_the_remaining_codes() {
+        let args = strings(&["app", "--progress=50%%", "%i", "--name=%c", "%k"]);
+        assert_eq!(
+            expand_field_codes(args, "App", Some("app-icon"), "/a.desktop"),
+            [
+                "app",
+                "--progress=50%",
+                "--icon",
+                "app-icon",
+                "--name=App",
+                "/a.desktop"
+            ]
+        );
+        // Without an icon, %i takes nothing with it
+        let args = strings(&["app", "%i"]);
+        assert_eq!(expand_field_codes(args, "App", None, "/a.desktop"), ["app"]);
+    }
+
+    #[test]
+    fn splits_a_leading_env() {
+        let (env, argv) = split_env(split_exec("env FOO=bar LANG=C.UTF-8 app --x=1"));
+        assert_eq!(
+            env,
+            [
+                ("FOO".to_string(), "bar".to_string()),
+                ("LANG".to_string(), "C.UTF-8".to_string())
+            ]
+        );
+        assert_eq!(argv, ["app", "--x=1"]);
+
+        let (env, argv) = split_env(split_exec("/usr/bin/env -i app"));
+        assert!(env.is_empty());
+        assert_eq!(argv, ["-i", "app"]);
+
+        // Only a leading env counts
+        let (env, argv) = split_env(split_exec("app FOO=bar"));
+        assert!(env.is_empty());
+        assert_eq!(argv, ["app", "FOO=bar"]);
+    }
+
+    #[test]
+    fn uses_each_terminals_own_arguments() {
+        assert_eq!(terminal_args("gnome-terminal"), ["--"]);
+        assert!(terminal_args("/usr/bin/kitty").is_empty());
+        assert_eq!(terminal_args("wezterm"), ["start", "--"]);
+        assert_eq!(terminal_args("my-terminal"), ["-e"]);
+    }
+}
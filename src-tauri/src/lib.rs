@@ -1,41 +1,459 @@
-mod startup;
-mod services;
+mod audit;
+mod background;
+mod boot;
+mod boot_history;
+mod bundle;
+mod cli;
+mod fingerprint;
+mod launch;
+mod profile;
+mod review;
+mod scan;
+mod snapshot;
+mod storage;
+mod task;
+mod timeline;
+mod trash;
+mod usage;
+mod watcher;
 
 use std::path::PathBuf;
+use tauri::Emitter;
+// The management core lives in its own crate, these keep the `crate::startup` paths working
+use zen_core::{exec, process, services, startup, unit_file, Error};
+
+/// Entry point when the binary is started with arguments. Returns the exit code.
+pub fn run_cli(args: Vec<String>) -> i32 {
+    cli::run(args)
+}
+
+/// Entry point for `--background`: no window, only notifications about new startup items.
+pub fn run_background() -> Result<(), Error> {
+    background::run()
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            let handle = app.handle().clone();
+            // Push entries other apps add or remove behind our back to the UI
+            if let Err(e) = watcher::spawn(move |change| {
+                let _ = handle.emit(change.kind.event_name(), &change.app);
+            }) {
+                storage::log_error("Could not watch startup folders", &e);
+            }
+            // Measuring waits on CPU sampling, don't hold up the window for it
+            std::thread::spawn(|| {
+                if let Err(e) = boot_history::record_current_boot() {
+                    storage::log_error("Could not record boot timings", &e);
+                }
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_apps, 
             toggle_app, 
             create_app, 
             delete_app,
-            services::get_system_services,
-            services::toggle_service
+            launch_app,
+            get_app_usage,
+            get_login_timeline,
+            get_boot_analysis,
+            record_boot,
+            list_boots,
+            compare_boots,
+            scan_startup_items,
+            scan_fingerprints,
+            get_fingerprints,
+            get_new_items,
+            acknowledge_items,
+            get_background_mode,
+            set_background_mode,
+            list_deleted,
+            restore_app,
+            purge,
+            get_trash_retention,
+            set_trash_retention,
+            create_snapshot,
+            list_snapshots,
+            delete_snapshot,
+            diff_snapshot,
+            restore_snapshot,
+            export_config,
+            preview_import,
+            import_config,
+            list_profiles,
+            save_profile,
+            delete_profile,
+            apply_profile,
+            get_history,
+            get_system_services,
+            toggle_service,
+            get_user_services,
+            toggle_user_service,
+            mask_service,
+            mask_user_service,
+            control_service,
+            control_user_service,
+            get_service_details,
+            cancel_task,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
+// Runs blocking work on the thread pool so the window stays responsive meanwhile
+async fn blocking<T, F>(work: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| Error::failed(e.to_string()))?
+}
+
+#[tauri::command]
+async fn get_apps() -> Result<Vec<startup::StartupApp>, Error> {
+    blocking(|| {
+        let mut apps = startup::get_startup_apps();
+        process::annotate(&mut apps);
+        Ok(apps)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn toggle_app(path: String, enable: bool) -> Result<(), Error> {
+    blocking(move || audit::toggle_app(PathBuf::from(path), enable)).await
+}
+
+#[tauri::command]
+async fn create_app(name: String, command: String, description: String) -> Result<(), Error> {
+    blocking(move || audit::create_app(name, command, description)).await
+}
+
+#[tauri::command]
+async fn delete_app(path: String) -> Result<(), Error> {
+    blocking(move || audit::delete_app(PathBuf::from(path))).await
+}
+
+#[tauri::command]
+async fn launch_app(path: String) -> Result<launch::LaunchResult, Error> {
+    // Waits a few seconds for output
+    blocking(move || launch::launch_app(PathBuf::from(path))).await
+}
+
+#[tauri::command]
+async fn get_app_usage() -> Result<Vec<usage::AppUsage>, Error> {
+    blocking(|| Ok(usage::get_app_usage())).await
+}
+
+#[tauri::command]
+async fn get_login_timeline() -> Result<timeline::LoginTimeline, Error> {
+    // Samples CPU activity for a moment
+    blocking(timeline::get_login_timeline).await
+}
+
+#[tauri::command]
+async fn get_boot_analysis() -> Result<boot::BootAnalysis, Error> {
+    blocking(boot::get_boot_analysis).await
+}
+
+#[tauri::command]
+async fn record_boot() -> Result<boot_history::BootSummary, Error> {
+    blocking(boot_history::record_current_boot).await
+}
+
+#[tauri::command]
+async fn list_boots() -> Result<Vec<boot_history::BootSummary>, Error> {
+    blocking(boot_history::list_boots).await
+}
+
+#[tauri::command]
+async fn compare_boots(
+    first: String,
+    second: String,
+) -> Result<boot_history::BootComparison, Error> {
+    blocking(move || boot_history::compare_boots(first, second)).await
+}
+
+#[tauri::command]
+async fn scan_startup_items(
+    app: tauri::AppHandle,
+    task_id: Option<String>,
+) -> Result<Vec<scan::Finding>, Error> {
+    // Asks the package manager about every executable, which can take a while
+    let task = task::start(task_id);
+    blocking(move || {
+        scan::scan_startup_items(
+            |done, total, item| {
+                let _ = app.emit(task::PROGRESS_EVENT, task.progress(done, total, item));
+            },
+            &task.cancel,
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+async fn scan_fingerprints(
+    app: tauri::AppHandle,
+    task_id: Option<String>,
+) -> Result<Vec<fingerprint::FingerprintChange>, Error> {
+    // Hashes every executable
+    let task = task::start(task_id);
+    blocking(move || {
+        fingerprint::scan_fingerprints(
+            |done, total, item| {
+                let _ = app.emit(task::PROGRESS_EVENT, task.progress(done, total, item));
+            },
+            &task.cancel,
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+fn get_fingerprints(path: String) -> Result<Option<fingerprint::ItemFingerprints>, Error> {
+    fingerprint::get_fingerprints(PathBuf::from(path))
+}
+
+#[tauri::command]
+async fn get_new_items() -> Result<Vec<review::StartupItem>, Error> {
+    blocking(review::get_new_items).await
+}
+
+#[tauri::command]
+fn acknowledge_items(keys: Option<Vec<String>>) -> Result<(), Error> {
+    review::acknowledge_items(keys)
+}
+
+#[tauri::command]
+fn get_background_mode() -> Result<bool, Error> {
+    background::get_background_mode()
+}
+
+#[tauri::command]
+fn set_background_mode(enabled: bool) -> Result<(), Error> {
+    background::set_background_mode(enabled)
+}
+
+#[tauri::command]
+async fn get_system_services() -> Result<Vec<services::Service>, Error> {
+    blocking(|| {
+        let mut list = services::get_system_services()?;
+        services::add_presets(&mut list, false);
+        Ok(list)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_user_services() -> Result<Vec<services::Service>, Error> {
+    blocking(|| {
+        let mut list = services::get_user_services()?;
+        services::add_presets(&mut list, true);
+        Ok(list)
+    })
+    .await
+}
+
+/// With `now` set the service is also started or stopped. The switch stays where it
+/// is when only that fails, the error comes back next to the state.
+///
+/// The call stays pending while the password dialog is open; cancelling its `task_id`
+/// stops waiting for it.
+#[tauri::command]
+async fn toggle_service(
+    name: String,
+    enable: bool,
+    now: Option<bool>,
+    task_id: Option<String>,
+) -> Result<audit::ToggleOutcome, Error> {
+    let task = task::start(task_id);
+    blocking(move || {
+        let now = now.unwrap_or(false);
+        audit::toggle_service_now(name, enable, now, false, &task.cancel)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn toggle_user_service(
+    name: String,
+    enable: bool,
+    now: Option<bool>,
+    task_id: Option<String>,
+) -> Result<audit::ToggleOutcome, Error> {
+    let task = task::start(task_id);
+    blocking(move || {
+        let now = now.unwrap_or(false);
+        audit::toggle_service_now(name, enable, now, true, &task.cancel)
+    })
+    .await
+}
+
+/// Starts, stops, restarts or reloads a service and returns the state it ended up in.
+#[tauri::command]
+async fn control_service(
+    name: String,
+    action: services::ServiceAction,
+    task_id: Option<String>,
+) -> Result<services::RuntimeState, Error> {
+    let task = task::start(task_id);
+    blocking(move || audit::control_service(name, action, &task.cancel)).await
+}
+
+#[tauri::command]
+async fn control_user_service(
+    name: String,
+    action: services::ServiceAction,
+    task_id: Option<String>,
+) -> Result<services::RuntimeState, Error> {
+    let task = task::start(task_id);
+    blocking(move || audit::control_user_service(name, action, &task.cancel)).await
+}
+
+/// Masks a service with `mask` set, unmasks it otherwise.
+#[tauri::command]
+async fn mask_service(name: String, mask: bool, task_id: Option<String>) -> Result<(), Error> {
+    let task = task::start(task_id);
+    blocking(move || audit::mask_service(name, mask, &task.cancel)).await
+}
+
+#[tauri::command]
+async fn mask_user_service(
+    name: String,
+    mask: bool,
+    task_id: Option<String>,
+) -> Result<(), Error> {
+    let task = task::start(task_id);
+    blocking(move || audit::mask_user_service(name, mask, &task.cancel)).await
+}
+
+/// The unit file and drop-ins of a service, merged, with where each value came from.
+#[tauri::command]
+async fn get_service_details(
+    name: String,
+    user: Option<bool>,
+) -> Result<unit_file::ServiceDetails, Error> {
+    blocking(move || unit_file::get_service_details(&name, user.unwrap_or(false))).await
+}
+
+/// Stops an operation started with this `task_id`. Returns false if it already finished.
+#[tauri::command]
+fn cancel_task(task_id: String) -> bool {
+    task::cancel(&task_id)
+}
+
+#[tauri::command]
+async fn get_history(
+    filter: Option<audit::HistoryFilter>,
+) -> Result<Vec<audit::AuditRecord>, Error> {
+    // Reads the whole log
+    blocking(move || audit::get_history(filter.unwrap_or_default())).await
+}
+
+#[tauri::command]
+fn list_deleted() -> Result<Vec<trash::DeletedApp>, Error> {
+    trash::list_deleted()
+}
+
+#[tauri::command]
+fn restore_app(id: String) -> Result<(), Error> {
+    audit::restore_app(id)
+}
+
+#[tauri::command]
+fn purge(id: Option<String>) -> Result<(), Error> {
+    trash::purge(id)
+}
+
+#[tauri::command]
+fn get_trash_retention() -> Result<Option<u64>, Error> {
+    trash::get_retention()
+}
+
+#[tauri::command]
+fn set_trash_retention(days: Option<u64>) -> Result<(), Error> {
+    trash::set_retention(days)
+}
+
+#[tauri::command]
+async fn create_snapshot(name: String) -> Result<snapshot::SnapshotSummary, Error> {
+    blocking(move || snapshot::create_snapshot(name)).await
+}
+
+#[tauri::command]
+async fn list_snapshots() -> Result<Vec<snapshot::SnapshotSummary>, Error> {
+    // Every snapshot file is read in full
+    blocking(snapshot::list_snapshots).await
+}
+
+#[tauri::command]
+fn delete_snapshot(id: String) -> Result<(), Error> {
+    snapshot::delete_snapshot(id)
+}
+
+#[tauri::command]
+async fn diff_snapshot(id: String) -> Result<Vec<snapshot::SnapshotChange>, Error> {
+    blocking(move || snapshot::diff_snapshot(id)).await
+}
+
+#[tauri::command]
+async fn restore_snapshot(
+    id: String,
+    keys: Option<Vec<String>>,
+    task_id: Option<String>,
+) -> Result<Vec<snapshot::ItemResult>, Error> {
+    let task = task::start(task_id);
+    blocking(move || snapshot::restore_snapshot(id, keys, &task.cancel)).await
+}
+
+#[tauri::command]
+async fn export_config(path: String) -> Result<(), Error> {
+    blocking(move || bundle::export_config(PathBuf::from(path))).await
+}
+
+#[tauri::command]
+async fn preview_import(path: String) -> Result<bundle::ImportPreview, Error> {
+    blocking(move || bundle::preview_import(PathBuf::from(path))).await
+}
+
+#[tauri::command]
+async fn import_config(
+    path: String,
+    keys: Option<Vec<String>>,
+    task_id: Option<String>,
+) -> Result<Vec<snapshot::ItemResult>, Error> {
+    let task = task::start(task_id);
+    blocking(move || bundle::import_config(PathBuf::from(path), keys, &task.cancel)).await
+}
+
 #[tauri::command]
-fn get_apps() -> Vec<startup::StartupApp> {
-    startup::get_startup_apps()
+fn list_profiles() -> Result<Vec<profile::Profile>, Error> {
+    profile::list_profiles()
 }
 
 #[tauri::command]
-fn toggle_app(path: String, enable: bool) -> Result<(), String> {
-    startup::toggle_app(PathBuf::from(path), enable)
+fn save_profile(profile: profile::Profile) -> Result<(), Error> {
+    profile::save_profile(profile)
 }
 
 #[tauri::command]
-fn create_app(name: String, command: String, description: String) -> Result<(), String> {
-    startup::create_app(name, command, description)
+fn delete_profile(name: String) -> Result<(), Error> {
+    profile::delete_profile(name)
 }
 
 #[tauri::command]
-fn delete_app(path: String) -> Result<(), String> {
-    startup::delete_app(PathBuf::from(path))
+async fn apply_profile(
+    name: String,
+    task_id: Option<String>,
+) -> Result<Vec<snapshot::ItemResult>, Error> {
+    let task = task::start(task_id);
+    blocking(move || profile::apply_profile(name, &task.cancel)).await
 }
@@ -2,5 +2,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Anywhere else the CLI rejects it like any other unknown option
+    if args == ["--background"] {
+        if let Err(e) = zen_app_manager_lib::run_background() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Any other arguments mean the CLI, the window only opens when started bare
+    if !args.is_empty() {
+        std::process::exit(zen_app_manager_lib::run_cli(args));
+    }
     zen_app_manager_lib::run()
 }
@@ -0,0 +1,139 @@
+use crate::audit;
+use crate::services;
+use crate::snapshot::ItemResult;
+use crate::startup;
+use crate::storage;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use zen_core::CancelToken;
+
+/// A named set of desired states. Items a profile doesn't mention are left alone.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub apps: BTreeMap<String, bool>, // Startup entry id -> enabled
+    #[serde(default)]
+    pub user_services: BTreeMap<String, bool>,
+    #[serde(default)]
+    pub system_services: BTreeMap<String, bool>,
+}
+
+fn profiles_file() -> Result<PathBuf, Error> {
+    Ok(storage::data_dir()?.join("profiles.json"))
+}
+
+pub fn list_profiles() -> Result<Vec<Profile>, Error> {
+    Ok(storage::read_json(&profiles_file()?)?.unwrap_or_default())
+}
+
+fn write_profiles(profiles: &[Profile]) -> Result<(), Error> {
+    storage::write_json(&profiles_file()?, &profiles)
+}
+
+/// Adds a profile, replacing any existing one with the same name.
+pub fn save_profile(profile: Profile) -> Result<(), Error> {
+    if profile.name.trim().is_empty() {
+        return Err(Error::parse("Profile name cannot be empty"));
+    }
+    let mut profiles = list_profiles()?;
+    profiles.retain(|p| p.name != profile.name);
+    profiles.push(profile);
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    write_profiles(&profiles)
+}
+
+pub fn delete_profile(name: String) -> Result<(), Error> {
+    let mut profiles = list_profiles()?;
+    let before = profiles.len();
+    profiles.retain(|p| p.name != name);
+    if profiles.len() == before {
+        return Err(Error::not_found(format!("No profile named {}", name)).with("profile", &name));
+    }
+    write_profiles(&profiles)
+}
+
+// Unit name -> unit-file state, e.g. "enabled" or "static"
+fn state_map(services: Result<Vec<services::Service>, Error>) -> HashMap<String, String> {
+    services
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| (s.name, s.state))
+        .collect()
+}
+
+/// Switches every item named in the profile to its desired state.
+/// System services are changed together so the password is only asked once.
+/// Once `cancel` is set, the remaining items are skipped.
+pub fn apply_profile(name: String, cancel: &CancelToken) -> Result<Vec<ItemResult>, Error> {
+    let profile = list_profiles()?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| {
+            Error::not_found(format!("No profile named {}", name)).with("profile", &name)
+        })?;
+    let mut results = Vec::new();
+
+    let apps = startup::get_startup_apps();
+    for (id, enable) in &profile.apps {
+        let result = match apps.iter().find(|a| &a.id == id) {
+            _ if cancel.is_cancelled() => Err(Error::cancelled()),
+            Some(app) if app.enabled == *enable => Ok(()),
+            Some(app) => audit::toggle_app(app.path.clone(), *enable),
+            None => Err(Error::not_found("Startup entry not found").with("id", id)),
+        };
+        results.push(ItemResult {
+            key: format!("app:{}", id),
+            error: result.err(),
+        });
+    }
+
+    let user_services = state_map(services::get_user_services());
+    for (name, enable) in &profile.user_services {
+        let result = match user_services.get(name) {
+            _ if cancel.is_cancelled() => Err(Error::cancelled()),
+            Some(current) if (current == "enabled") == *enable => Ok(()),
+            Some(_) => audit::toggle_user_service(name.clone(), *enable, cancel),
+            None => Err(Error::not_found("Service not found").with("unit", name)),
+        };
+        results.push(ItemResult {
+            key: format!("user-service:{}", name),
+            error: result.err(),
+        });
+    }
+
+    let system_services = state_map(services::get_system_services());
+    let mut changes = Vec::new();
+    for (name, enable) in &profile.system_services {
+        let key = format!("service:{}", name);
+        match system_services.get(name) {
+            Some(current) if (current == "enabled") == *enable => {
+                results.push(ItemResult { key, error: None })
+            }
+            Some(_) => changes.push((name.clone(), *enable)),
+            None => results.push(ItemResult {
+                key,
+                error: Some(Error::not_found("Service not found").with("unit", name)),
+            }),
+        }
+    }
+    let changed = services::set_services_enabled(&changes, false, cancel);
+    for ((name, enable), result) in changes.iter().zip(changed) {
+        audit::record(
+            "toggle_service",
+            name,
+            system_services.get(name).cloned(),
+            Some(audit::enabled_label(*enable)),
+            services::privilege_path(),
+            &result,
+        );
+        results.push(ItemResult {
+            key: format!("service:{}", name),
+            error: result.err(),
+        });
+    }
+
+    Ok(results)
+}
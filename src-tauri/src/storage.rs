@@ -0,0 +1,102 @@
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Same folder Tauri uses for `app_data_dir`, so everything Zen keeps lives in one place
+const APP_IDENTIFIER: &str = "io.github.d0ksan8.zen-app-manager";
+
+pub fn data_dir() -> Result<PathBuf, Error> {
+    dirs::data_dir()
+        .map(|d| d.join(APP_IDENTIFIER))
+        .ok_or_else(|| Error::not_found("Could not find data directory"))
+}
+
+// errors.log starts over once it grows past this
+const ERROR_LOG_LIMIT: u64 = 256 * 1024;
+
+/// Appends a failure that has no window to show up in, e.g. during setup or in
+/// background mode, to `errors.log` in the data folder. Logging never fails itself.
+pub fn log_error(what: &str, error: &dyn std::fmt::Display) {
+    let Ok(dir) = data_dir() else {
+        return;
+    };
+    let _ = fs::create_dir_all(&dir);
+    let path = dir.join("errors.log");
+    if fs::metadata(&path).is_ok_and(|m| m.len() > ERROR_LOG_LIMIT) {
+        let _ = fs::remove_file(&path);
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(file, "{} {}: {}", now(), what, error);
+    }
+}
+
+/// Returns `<data dir>/<name>`, creating it if needed.
+pub fn subdir(name: &str) -> Result<PathBuf, Error> {
+    let dir = data_dir()?.join(name);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| Error::io(e, &dir))?;
+    }
+    Ok(dir)
+}
+
+/// Reads a JSON file, returning `None` if it doesn't exist yet.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map(Some).map_err(|e| {
+            Error::parse(format!("{}: {}", path.display(), e)).with("path", path.display())
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::io(e, path)),
+    }
+}
+
+/// Writes a JSON file through a temporary file so a crash never leaves half a record behind.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let content = serde_json::to_string_pretty(value).map_err(|e| Error::failed(e.to_string()))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::io(e, parent))?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content).map_err(|e| Error::io(e, path))?;
+    fs::rename(&tmp, path).map_err(|e| Error::io(e, path))
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Builds a unique, filesystem-safe record id from the current time and a label.
+pub fn new_id(label: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let label: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("{}-{}", nanos, label)
+}
+
+/// Rejects ids that could escape their storage folder.
+pub fn check_id(id: &str) -> Result<(), Error> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        return Err(Error::parse(format!("Invalid id: {}", id)).with("id", id));
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use zen_core::CancelToken;
+
+/// Event carrying a `Progress` payload while a long operation runs.
+pub const PROGRESS_EVENT: &str = "task-progress";
+
+// Operations that can still be cancelled, by the id the frontend gave them
+static RUNNING: Mutex<BTreeMap<String, CancelToken>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Progress {
+    pub task: Option<String>, // Id passed to the command, if any
+    pub done: usize,
+    pub total: usize,
+    pub item: String, // What is being worked on right now
+}
+
+/// One running operation. Its id stays cancellable until the task is dropped.
+pub struct Task {
+    id: Option<String>,
+    pub cancel: CancelToken,
+}
+
+impl Task {
+    pub fn progress(&self, done: usize, total: usize, item: &str) -> Progress {
+        Progress {
+            task: self.id.clone(),
+            done,
+            total,
+            item: item.to_string(),
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        if let (Some(id), Ok(mut running)) = (&self.id, RUNNING.lock()) {
+            running.remove(id);
+        }
+    }
+}
+
+/// Starts tracking an operation. Without an id it just can't be cancelled.
+pub fn start(id: Option<String>) -> Task {
+    let cancel = CancelToken::new();
+    if let (Some(id), Ok(mut running)) = (&id, RUNNING.lock()) {
+        running.insert(id.clone(), cancel.clone());
+    }
+    Task { id, cancel }
+}
+
+/// Asks a running operation to stop. Returns false if it already finished.
+pub fn cancel(id: &str) -> bool {
+    let token = RUNNING.lock().ok().and_then(|r| r.get(id).cloned());
+    match token {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,166 @@
+// Only Linux exposes /proc, elsewhere most of this goes unused
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use crate::process::{self, ProcessInfo};
+#[cfg(target_os = "linux")]
+use crate::startup;
+use crate::startup::StartupApp;
+use crate::usage::{self, AppUsage};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::Duration;
+
+// How long we watch CPU time to tell whether an app is still busy
+const SAMPLE_WINDOW: Duration = Duration::from_millis(500);
+// Share of one core above which an app counts as still busy
+const BUSY_THRESHOLD: f64 = 0.05;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoginTimeline {
+    pub session_start: f64, // Seconds since boot
+    pub source: String,     // "session-leader" or "user-manager"
+    pub now: f64,           // Seconds after session_start, the right edge of the chart
+    pub entries: Vec<TimelineEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimelineEntry {
+    pub id: String,
+    pub name: String,
+    pub running: bool,
+    pub started: Option<f64>, // Seconds after session start the oldest process appeared
+    pub settled: Option<f64>, // Estimated end of the startup burst, None while still busy
+    pub cpu_time_secs: f64,
+    pub pids: Vec<u32>,
+}
+
+fn start_secs(process: &ProcessInfo) -> f64 {
+    process.start_ticks as f64 / process::clock_ticks() as f64
+}
+
+// The process logind recorded as leader of our session
+fn session_leader() -> Option<ProcessInfo> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    let output = Command::new("loginctl")
+        .args(["show-session", &session, "--property=Leader", "--value"])
+        .output()
+        .ok()?;
+    let pid = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    // Usually owned by root, which still leaves /proc/<pid>/stat readable
+    process::read_process(pid)
+}
+
+/// When the graphical session started, in seconds since boot, and where that came from.
+/// Falls back to the `systemd --user` instance when logind can't tell us.
+pub fn session_start(processes: &[ProcessInfo]) -> Option<(f64, &'static str)> {
+    if let Some(leader) = session_leader() {
+        return Some((start_secs(&leader), "session-leader"));
+    }
+    processes
+        .iter()
+        .filter(|p| {
+            p.cmdline.first().is_some_and(|a| a.ends_with("systemd"))
+                && p.cmdline.iter().any(|a| a == "--user")
+        })
+        .map(start_secs)
+        .min_by(|a, b| a.total_cmp(b))
+        .map(|start| (start, "user-manager"))
+}
+
+fn timeline_entry(
+    app: &StartupApp,
+    processes: &[ProcessInfo],
+    session_start: f64,
+    before: Option<&AppUsage>,
+    after: Option<&AppUsage>,
+) -> TimelineEntry {
+    let started = process::matching_processes(app, processes)
+        .iter()
+        .map(|(p, _)| start_secs(p))
+        .min_by(|a, b| a.total_cmp(b))
+        .map(|start| start - session_start);
+
+    let cpu_time_secs = after.map(|u| u.cpu_time_secs).unwrap_or(0.0);
+    let busy = match (before, after) {
+        (Some(before), Some(after)) => {
+            let used = after.cpu_time_secs - before.cpu_time_secs;
+            used / SAMPLE_WINDOW.as_secs_f64() > BUSY_THRESHOLD
+        }
+        _ => false,
+    };
+
+    // /proc keeps no history, so assume the CPU time was spent in one burst right after launch
+    let settled = started.filter(|_| !busy).map(|start| start + cpu_time_secs);
+
+    TimelineEntry {
+        id: app.id.clone(),
+        name: app.name.clone(),
+        running: after.is_some(),
+        started,
+        settled,
+        cpu_time_secs,
+        pids: after.map(|u| u.pids.clone()).unwrap_or_default(),
+    }
+}
+
+/// Builds the login timeline of every enabled startup entry.
+/// Blocks for a short moment while CPU activity is sampled.
+#[cfg(target_os = "linux")]
+pub fn get_login_timeline() -> Result<LoginTimeline, Error> {
+    let processes = process::list_processes();
+    let (session_start, source) = session_start(&processes)
+        .ok_or_else(|| Error::failed("Could not determine when the session started"))?;
+    let apps: Vec<StartupApp> = startup::get_startup_apps()
+        .into_iter()
+        .filter(|a| a.enabled)
+        .collect();
+
+    let sample = |processes: &[ProcessInfo]| -> Vec<Option<AppUsage>> {
+        let sampled_at = process::system_uptime().unwrap_or(0.0);
+        apps.iter()
+            .map(|app| usage::app_usage(app, processes, sampled_at))
+            .collect()
+    };
+    let before = sample(&processes);
+    std::thread::sleep(SAMPLE_WINDOW);
+    let processes = process::list_processes();
+    let after = sample(&processes);
+
+    let mut entries: Vec<TimelineEntry> = apps
+        .iter()
+        .zip(before.iter().zip(after.iter()))
+        .map(|(app, (before, after))| {
+            timeline_entry(
+                app,
+                &processes,
+                session_start,
+                before.as_ref(),
+                after.as_ref(),
+            )
+        })
+        .collect();
+    // Launched items first, in launch order, then whatever never started
+    entries.sort_by(|a, b| match (a.started, b.started) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.name.cmp(&b.name),
+    });
+
+    let now = process::system_uptime().unwrap_or(session_start) - session_start;
+    Ok(LoginTimeline {
+        session_start,
+        source: source.to_string(),
+        now,
+        entries,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_login_timeline() -> Result<LoginTimeline, Error> {
+    Err(Error::unsupported("Not supported on this OS"))
+}
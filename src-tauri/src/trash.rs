@@ -0,0 +1,212 @@
+use crate::startup::{self, StartupApp};
+use crate::storage;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ENTRY_FILE: &str = "entry.json";
+const PAYLOAD_FILE: &str = "payload";
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletedApp {
+    pub id: String,
+    pub app: StartupApp, // The entry as it was listed when it got deleted
+    pub original_path: PathBuf,
+    pub deleted_at: u64,
+    // Registry entries have no file to keep, so their command is stored here instead
+    pub registry_value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct TrashSettings {
+    retention_days: Option<u64>,
+}
+
+fn trash_dir() -> Result<PathBuf, Error> {
+    storage::subdir("trash")
+}
+
+fn entry_dir(id: &str) -> Result<PathBuf, Error> {
+    storage::check_id(id)?;
+    let dir = trash_dir()?.join(id);
+    if !dir.join(ENTRY_FILE).exists() {
+        return Err(Error::not_found(format!("No deleted entry with id {}", id)).with("id", id));
+    }
+    Ok(dir)
+}
+
+// Rename keeps permissions and timestamps; copying is only the fallback across filesystems
+fn move_file(from: &Path, to: &Path) -> Result<(), Error> {
+    fs::rename(from, to)
+        .or_else(|_| fs::copy(from, to).and_then(|_| fs::remove_file(from)))
+        .map_err(|e| Error::io(e, from))
+}
+
+fn describe(path: &Path) -> StartupApp {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+    StartupApp {
+        id: file_name.clone(),
+        name: file_name,
+        command: String::new(),
+        full_command: String::new(),
+        enabled: false,
+        path: path.to_path_buf(),
+        size: "Unknown".to_string(),
+        location: "Unknown".to_string(),
+        publisher: "Unknown".to_string(),
+        runtime: None,
+    }
+}
+
+/// Moves a startup entry into Zen's trash instead of deleting it outright.
+pub fn trash_app(path: PathBuf) -> Result<DeletedApp, Error> {
+    let app = startup::get_startup_apps()
+        .into_iter()
+        .find(|a| a.path == path)
+        .unwrap_or_else(|| describe(&path));
+
+    let id = storage::new_id(&app.id);
+    let dir = trash_dir()?.join(&id);
+    fs::create_dir_all(&dir).map_err(|e| Error::io(e, &dir))?;
+
+    // The entry is written before anything moves, so a failure never strands a payload
+    // where listing and restoring can't see it
+    let registry_value = if startup::is_registry_path(&path) {
+        match startup::read_registry_value(&path) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
+    let entry = DeletedApp {
+        id,
+        app,
+        original_path: path.clone(),
+        deleted_at: storage::now(),
+        registry_value,
+    };
+    let removed = storage::write_json(&dir.join(ENTRY_FILE), &entry).and_then(|_| {
+        if entry.registry_value.is_some() {
+            startup::delete_app(path.clone())
+        } else {
+            move_file(&path, &dir.join(PAYLOAD_FILE))
+        }
+    });
+    if let Err(e) = removed {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+
+    let _ = purge_expired();
+    Ok(entry)
+}
+
+pub fn list_deleted() -> Result<Vec<DeletedApp>, Error> {
+    purge_expired()?;
+
+    let mut entries = Vec::new();
+    let trash = trash_dir()?;
+    for dir in fs::read_dir(&trash)
+        .map_err(|e| Error::io(e, &trash))?
+        .flatten()
+    {
+        if let Ok(Some(entry)) = storage::read_json::<DeletedApp>(&dir.path().join(ENTRY_FILE)) {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+    Ok(entries)
+}
+
+/// Reads one trashed entry.
+pub fn deleted_entry(id: &str) -> Result<DeletedApp, Error> {
+    let dir = entry_dir(id)?;
+    storage::read_json(&dir.join(ENTRY_FILE))?
+        .ok_or_else(|| Error::not_found(format!("No deleted entry with id {}", id)).with("id", id))
+}
+
+/// Puts a trashed entry back where it came from.
+pub fn restore_app(id: String) -> Result<(), Error> {
+    let dir = entry_dir(&id)?;
+    let entry = deleted_entry(&id)?;
+
+    match &entry.registry_value {
+        Some(value) => startup::write_registry_value(&entry.original_path, value)?,
+        None => {
+            if entry.original_path.exists() {
+                return Err(Error::failed(format!(
+                    "An entry already exists at {}",
+                    entry.original_path.display()
+                ))
+                .with("path", entry.original_path.display()));
+            }
+            if let Some(parent) = entry.original_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error::io(e, parent))?;
+            }
+            move_file(&dir.join(PAYLOAD_FILE), &entry.original_path)?;
+        }
+    }
+
+    fs::remove_dir_all(&dir).map_err(|e| Error::io(e, &dir))
+}
+
+/// Permanently removes one trashed entry, or all of them when `id` is `None`.
+pub fn purge(id: Option<String>) -> Result<(), Error> {
+    match id {
+        Some(id) => {
+            let dir = entry_dir(&id)?;
+            fs::remove_dir_all(&dir).map_err(|e| Error::io(e, &dir))
+        }
+        None => {
+            for entry in list_deleted()? {
+                let dir = trash_dir()?.join(&entry.id);
+                fs::remove_dir_all(&dir).map_err(|e| Error::io(e, &dir))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn get_retention() -> Result<Option<u64>, Error> {
+    let settings: TrashSettings =
+        storage::read_json(&trash_dir()?.join(SETTINGS_FILE))?.unwrap_or_default();
+    Ok(settings.retention_days)
+}
+
+/// Sets how many days entries stay in the trash; `None` keeps them until purged by hand.
+pub fn set_retention(days: Option<u64>) -> Result<(), Error> {
+    let settings = TrashSettings {
+        retention_days: days,
+    };
+    storage::write_json(&trash_dir()?.join(SETTINGS_FILE), &settings)?;
+    purge_expired()
+}
+
+fn purge_expired() -> Result<(), Error> {
+    let Some(days) = get_retention()? else {
+        return Ok(());
+    };
+    let cutoff = storage::now().saturating_sub(days.saturating_mul(24 * 60 * 60));
+
+    let trash = trash_dir()?;
+    for dir in fs::read_dir(&trash)
+        .map_err(|e| Error::io(e, &trash))?
+        .flatten()
+    {
+        if let Ok(Some(entry)) = storage::read_json::<DeletedApp>(&dir.path().join(ENTRY_FILE)) {
+            if entry.deleted_at < cutoff {
+                fs::remove_dir_all(dir.path()).map_err(|e| Error::io(e, &dir.path()))?;
+            }
+        }
+    }
+    Ok(())
+}
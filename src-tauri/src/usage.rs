@@ -0,0 +1,147 @@
+// Only Linux exposes /proc, elsewhere most of this goes unused
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use crate::process::{self, ProcessInfo};
+#[cfg(target_os = "linux")]
+use crate::startup;
+use crate::startup::StartupApp;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppUsage {
+    pub id: String,
+    pub name: String,
+    pub pids: Vec<u32>, // Matched processes plus all of their children
+    pub rss_bytes: u64,
+    pub cpu_time_secs: f64, // User + system time, including reaped children
+    pub read_bytes: u64,    // Actually read from disk, not served from cache
+    pub write_bytes: u64,
+    pub io_available: bool, // False when /proc/<pid>/io couldn't be read for some process
+    pub sampled_at: f64,    // Seconds since boot, to turn two polls into rates
+}
+
+#[derive(Debug, Default)]
+struct ProcessUsage {
+    rss_bytes: u64,
+    cpu_ticks: u64,
+    io: Option<(u64, u64)>,
+}
+
+fn read_usage(pid: u32) -> Option<ProcessUsage> {
+    let (_, fields) = process::stat_fields(pid)?;
+    // utime, stime, cutime, cstime are fields 14-17
+    let cpu_ticks = fields
+        .get(11..15)?
+        .iter()
+        .filter_map(|f| f.parse::<i64>().ok())
+        .map(|t| t.max(0) as u64)
+        .sum();
+
+    let rss_kb = fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|l| l.strip_prefix("VmRSS:"))
+                .and_then(|v| v.split_whitespace().next()?.parse::<u64>().ok())
+        })
+        .unwrap_or(0);
+
+    let io = fs::read_to_string(format!("/proc/{}/io", pid))
+        .ok()
+        .and_then(|io| {
+            let value = |key: &str| {
+                io.lines()
+                    .find_map(|l| l.strip_prefix(key))
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+            };
+            Some((value("read_bytes:")?, value("write_bytes:")?))
+        });
+
+    Some(ProcessUsage {
+        rss_bytes: rss_kb * 1024,
+        cpu_ticks,
+        io,
+    })
+}
+
+/// The given processes and all of their descendants.
+fn with_descendants(roots: &[u32], processes: &[ProcessInfo]) -> BTreeSet<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for p in processes {
+        children.entry(p.ppid).or_default().push(p.pid);
+    }
+
+    let mut all = BTreeSet::new();
+    let mut stack: Vec<u32> = roots.to_vec();
+    while let Some(pid) = stack.pop() {
+        if all.insert(pid) {
+            if let Some(kids) = children.get(&pid) {
+                stack.extend(kids);
+            }
+        }
+    }
+    all
+}
+
+/// Usage of one entry's process tree, or None when it is not running.
+pub fn app_usage(app: &StartupApp, processes: &[ProcessInfo], sampled_at: f64) -> Option<AppUsage> {
+    let roots: Vec<u32> = process::matching_processes(app, processes)
+        .iter()
+        .map(|(p, _)| p.pid)
+        .collect();
+    if roots.is_empty() {
+        return None;
+    }
+
+    let pids = with_descendants(&roots, processes);
+    let mut usage = AppUsage {
+        id: app.id.clone(),
+        name: app.name.clone(),
+        pids: Vec::new(),
+        rss_bytes: 0,
+        cpu_time_secs: 0.0,
+        read_bytes: 0,
+        write_bytes: 0,
+        io_available: true,
+        sampled_at,
+    };
+    let mut cpu_ticks = 0;
+
+    // Processes may exit while we read them, those are simply skipped
+    for pid in pids {
+        let Some(p) = read_usage(pid) else {
+            continue;
+        };
+        usage.pids.push(pid);
+        usage.rss_bytes += p.rss_bytes;
+        cpu_ticks += p.cpu_ticks;
+        match p.io {
+            Some((read, write)) => {
+                usage.read_bytes += read;
+                usage.write_bytes += write;
+            }
+            None => usage.io_available = false,
+        }
+    }
+    usage.cpu_time_secs = cpu_ticks as f64 / process::clock_ticks() as f64;
+    Some(usage)
+}
+
+/// Resource usage of every running startup entry. Cheap enough to poll every few seconds.
+#[cfg(target_os = "linux")]
+pub fn get_app_usage() -> Vec<AppUsage> {
+    let processes = process::list_processes();
+    let sampled_at = process::system_uptime().unwrap_or(0.0);
+    startup::get_startup_apps()
+        .iter()
+        .filter_map(|app| app_usage(app, &processes, sampled_at))
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_app_usage() -> Vec<AppUsage> {
+    Vec::new()
+}
@@ -0,0 +1,110 @@
+use crate::startup::{self, StartupApp};
+use crate::Error;
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// Apps often write a file several times in a row, wait this long for things to settle
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl ChangeKind {
+    /// Name of the Tauri event carrying this kind of change.
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "startup-app-added",
+            ChangeKind::Changed => "startup-app-changed",
+            ChangeKind::Removed => "startup-app-removed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppChange {
+    pub kind: ChangeKind,
+    pub app: StartupApp, // Removed entries carry their last known state
+}
+
+fn index(apps: Vec<StartupApp>) -> HashMap<PathBuf, StartupApp> {
+    apps.into_iter().map(|a| (a.path.clone(), a)).collect()
+}
+
+/// Compares two listings and reports what was added, changed or removed.
+pub fn diff_apps(
+    before: &HashMap<PathBuf, StartupApp>,
+    after: &HashMap<PathBuf, StartupApp>,
+) -> Vec<AppChange> {
+    let mut changes = Vec::new();
+    for (path, app) in after {
+        match before.get(path) {
+            None => changes.push(AppChange {
+                kind: ChangeKind::Added,
+                app: app.clone(),
+            }),
+            Some(old) if old != app => changes.push(AppChange {
+                kind: ChangeKind::Changed,
+                app: app.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (path, app) in before {
+        if !after.contains_key(path) {
+            changes.push(AppChange {
+                kind: ChangeKind::Removed,
+                app: app.clone(),
+            });
+        }
+    }
+    changes
+}
+
+/// Watches every startup source directory on a background thread and calls
+/// `on_change` for each entry that was added, changed or removed.
+pub fn spawn<F>(on_change: F) -> Result<(), Error>
+where
+    F: Fn(AppChange) + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| Error::failed(e.to_string()))?;
+
+    for dir in startup::source_dirs() {
+        // Watch the folder even before anything autostarts, so the first entry is noticed
+        if !dir.exists() {
+            fs::create_dir_all(&dir).map_err(|e| Error::io(e, &dir))?;
+        }
+        watcher
+            .watch(&dir, RecursiveMode::Recursive)
+            .map_err(|e| Error::failed(e.to_string()).with("path", dir.display()))?;
+    }
+
+    std::thread::spawn(move || {
+        // The watcher stops when dropped, so it lives as long as this thread
+        let _watcher = watcher;
+        let mut known = index(startup::get_startup_apps());
+
+        while rx.recv().is_ok() {
+            // Coalesce bursts of events into a single rescan
+            while rx.recv_timeout(SETTLE_TIME).is_ok() {}
+
+            let current = index(startup::get_startup_apps());
+            for change in diff_apps(&known, &current) {
+                on_change(change);
+            }
+            known = current;
+        }
+    });
+
+    Ok(())
+}
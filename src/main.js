@@ -1,7 +1,39 @@
 const { invoke } = window.__TAURI__.core;
+const { listen } = window.__TAURI__.event;
 const { getCurrentWindow } = window.__TAURI__.window;
 const appWindow = getCurrentWindow();
 
+// Commands reject with { code, message, context }; plugins may still reject with plain strings
+function errorMessage(error) {
+  if (!error || typeof error !== 'object' || !error.code) {
+    return String(error);
+  }
+  const context = error.context || {};
+  switch (error.code) {
+    case 'auth_cancelled':
+      return 'Authentication was cancelled.';
+    case 'permission_denied':
+      return context.elevation === 'administrator'
+        ? 'Access denied. Run Zen App Manager as Administrator to change system items.'
+        : 'Permission denied: ' + error.message;
+    case 'tool_missing':
+      return (context.tool || 'A required tool') + ' is not installed.';
+    case 'unsupported':
+      return 'Not available on this system: ' + error.message;
+    default:
+      return error.message;
+  }
+}
+
+// Dismissing the password prompt or cancelling is a choice, not something to warn about
+function showError(prefix, error) {
+  console.error(prefix, error);
+  if (error && (error.code === 'auth_cancelled' || error.code === 'cancelled')) {
+    return;
+  }
+  alert(prefix + ": " + errorMessage(error));
+}
+
 // Window Controls
 document.getElementById('titlebar-minimize').addEventListener('click', () => appWindow.minimize());
 document.getElementById('titlebar-maximize').addEventListener('click', async () => {
@@ -95,6 +127,21 @@ function renderApps(apps) {
     meta.appendChild(locationBadge);
     meta.appendChild(sizeBadge);
 
+    // Runtime Badge (Linux only, flags entries that crashed or were started elsewhere)
+    if (app.runtime) {
+      const runtimeBadge = document.createElement("span");
+      runtimeBadge.className = "meta-badge";
+      if (app.runtime.running) {
+        runtimeBadge.textContent = app.enabled ? "Running" : "Running (started elsewhere)";
+        runtimeBadge.style.color = "#22c55e";
+        runtimeBadge.title = `PID ${app.runtime.pids.join(", ")}`;
+      } else {
+        runtimeBadge.textContent = app.enabled ? "Not running" : "Stopped";
+        runtimeBadge.style.color = app.enabled ? "#f59e0b" : "#94a3b8";
+      }
+      meta.appendChild(runtimeBadge);
+    }
+
     // Command (Path) - Make it smaller/subtle
     const command = document.createElement("div");
     command.className = "app-command";
@@ -166,7 +213,11 @@ async function loadServices() {
     const services = await invoke("get_system_services");
     renderServices(services);
   } catch (error) {
-    serviceListEl.innerHTML = `<p style="color: red;">Error: ${error}</p>`;
+    serviceListEl.innerHTML = "";
+    const message = document.createElement("p");
+    message.style.color = "red";
+    message.textContent = "Error: " + errorMessage(error);
+    serviceListEl.appendChild(message);
   }
 }
 
@@ -191,6 +242,9 @@ function renderServices(services) {
       const name = document.createElement("div");
       name.className = "app-name";
       name.textContent = service.name;
+      if (service.description) {
+        name.title = service.description;
+      }
 
       const meta = document.createElement("div");
       meta.className = "app-meta";
@@ -199,9 +253,36 @@ function renderServices(services) {
       stateBadge.className = "meta-badge";
       stateBadge.textContent = service.state;
       stateBadge.style.color = service.state === 'enabled' ? '#22c55e' : '#94a3b8';
+      if (service.masked) {
+        stateBadge.style.color = '#ef4444';
+        stateBadge.title = "Masked: nothing can start this service until it is unmasked";
+      }
 
       meta.appendChild(stateBadge);
 
+      // What the unit is doing right now, independent of whether it starts at boot
+      const activeBadge = document.createElement("span");
+      activeBadge.className = "meta-badge";
+      const showRuntime = (runtime) => {
+        service.active_state = runtime.active_state;
+        service.sub_state = runtime.sub_state;
+        activeBadge.style.display = runtime.active_state ? "" : "none";
+        activeBadge.textContent = runtime.sub_state
+          ? runtime.active_state + " (" + runtime.sub_state + ")"
+          : runtime.active_state;
+        activeBadge.style.color = runtime.active_state === 'active' ? '#22c55e'
+          : runtime.active_state === 'failed' ? '#ef4444' : '';
+      };
+      showRuntime(service);
+      meta.appendChild(activeBadge);
+
+      if (service.preset && service.preset !== service.state) {
+        const presetBadge = document.createElement("span");
+        presetBadge.className = "meta-badge";
+        presetBadge.textContent = "preset: " + service.preset;
+        meta.appendChild(presetBadge);
+      }
+
       info.appendChild(name);
       info.appendChild(meta);
 
@@ -214,18 +295,37 @@ function renderServices(services) {
       const input = document.createElement("input");
       input.type = "checkbox";
       input.checked = service.state === 'enabled';
+      // Static, masked, generated and similar units have nothing to enable or disable
+      input.disabled = service.state !== 'enabled' && service.state !== 'disabled';
       input.onchange = async () => {
         const originalState = input.checked;
+        input.disabled = true; // The command stays pending while the password prompt is open
         try {
-          await invoke("toggle_service", { name: service.name, enable: input.checked });
+          const outcome = await invoke("toggle_service", {
+            name: service.name,
+            enable: input.checked,
+            now: document.getElementById("services-apply-now").checked,
+            taskId: "toggle-service-" + service.name,
+          });
+          if (outcome.state) {
+            showRuntime(outcome.state);
+            updateControls();
+          }
           // Update the local state description if needed
           stateBadge.textContent = input.checked ? 'enabled' : 'disabled';
           stateBadge.style.color = input.checked ? '#22c55e' : '#94a3b8';
+          // The switch stays, only starting or stopping right away failed
+          if (outcome.control_error) {
+            showError(input.checked ? "Enabled, but failed to start service"
+              : "Disabled, but failed to stop service", outcome.control_error);
+          }
         } catch (err) {
-          alert("Failed to toggle service: " + err);
+          showError("Failed to toggle service", err);
           input.checked = !originalState; // Revert switch
           stateBadge.textContent = !originalState ? 'enabled' : 'disabled';
           stateBadge.style.color = !originalState ? '#22c55e' : '#94a3b8';
+        } finally {
+          input.disabled = false;
         }
       };
 
@@ -235,6 +335,59 @@ function renderServices(services) {
       switchLabel.appendChild(input);
       switchLabel.appendChild(slider);
 
+      // Start/Stop and Restart act right away and don't change what happens at boot
+      const runBtn = document.createElement("button");
+      runBtn.className = "service-btn";
+      const restartBtn = document.createElement("button");
+      restartBtn.className = "service-btn";
+      restartBtn.textContent = "Restart";
+      const updateControls = () => {
+        const running = service.active_state === 'active' || service.active_state === 'reloading';
+        runBtn.textContent = running ? "Stop" : "Start";
+        restartBtn.style.display = running ? "" : "none";
+        runBtn.disabled = service.masked && !running;
+      };
+      const control = async (button, action) => {
+        button.disabled = true;
+        try {
+          showRuntime(await invoke("control_service", {
+            name: service.name,
+            action,
+            taskId: "control-service-" + service.name,
+          }));
+        } catch (err) {
+          showError("Failed to " + action + " service", err);
+        } finally {
+          button.disabled = false;
+          updateControls();
+        }
+      };
+      runBtn.onclick = () => control(runBtn, runBtn.textContent === "Stop" ? "stop" : "start");
+      restartBtn.onclick = () => control(restartBtn, "restart");
+      updateControls();
+
+      // Masking also stops other units from pulling the service in
+      const maskBtn = document.createElement("button");
+      maskBtn.className = "service-btn";
+      maskBtn.textContent = service.masked ? "Unmask" : "Mask";
+      maskBtn.onclick = async () => {
+        maskBtn.disabled = true;
+        try {
+          await invoke("mask_service", {
+            name: service.name,
+            mask: !service.masked,
+            taskId: "mask-service-" + service.name,
+          });
+          loadServices(); // Unmasking brings back whatever state the unit file had
+        } catch (err) {
+          showError(service.masked ? "Failed to unmask service" : "Failed to mask service", err);
+          maskBtn.disabled = false;
+        }
+      };
+
+      actions.appendChild(restartBtn);
+      actions.appendChild(runBtn);
+      actions.appendChild(maskBtn);
       actions.appendChild(switchLabel);
       card.appendChild(info);
       card.appendChild(actions);
@@ -257,8 +410,7 @@ async function toggleApp(path, enabled) {
   try {
     await invoke("toggle_app", { path, enable: enabled });
   } catch (error) {
-    console.error("Failed to toggle app:", error);
-    alert("Failed to toggle app: " + error); // Show error to user
+    showError("Failed to toggle app", error); // Show error to user
     loadApps(); // Revert UI on error
   }
 }
@@ -284,8 +436,7 @@ confirmYesBtn.onclick = async () => {
       await invoke("delete_app", { path: appToDelete });
       loadApps();
     } catch (error) {
-      console.error("Failed to delete app:", error);
-      alert("Failed to delete app: " + error);
+      showError("Failed to delete app", error);
     }
     confirmModal.classList.remove("active");
     appToDelete = null;
@@ -319,8 +470,7 @@ addForm.onsubmit = async (e) => {
     addForm.reset();
     loadApps();
   } catch (error) {
-    console.error("Failed to create app:", error);
-    alert("Failed to create app: " + error);
+    showError("Failed to create app", error);
   }
 };
 
@@ -337,6 +487,11 @@ window.onclick = (event) => {
 
 loadApps();
 
+// Refresh automatically when other apps add, change or remove startup entries
+["startup-app-added", "startup-app-changed", "startup-app-removed"].forEach((event) => {
+  listen(event, () => loadApps());
+});
+
 // File Picker Logic - Optional for users who want to browse
 const browseBtn = document.getElementById("browse-btn");
 if (browseBtn) {
@@ -365,7 +520,7 @@ if (browseBtn) {
         }
       }
     } catch (error) {
-      alert("Error: " + error);
+      showError("Error", error);
     }
   };
 }